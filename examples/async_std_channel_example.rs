@@ -1,7 +1,7 @@
 use async_std::{
     pin::Pin,
     prelude::*,
-    stream, task,
+    task,
     task::{Context, Poll},
 };
 use std::time::Duration;
@@ -25,7 +25,7 @@ impl SlowCounter {
 impl Stream for SlowCounter {
     type Item = usize;
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         Poll::Ready(Some(task::block_on(self.get_mut().count())))
     }
 }
@@ -1,24 +1,21 @@
 //! # PID Controller WorkerPool
 //!
-//! Attempting to drive target HTTP request throughput via PID controller.
+//! Attempting to drive target HTTP request throughput via PID controller, with a
+//! `GradientController` watching request latency and holding the pool back if it climbs.
 //!
 
 use async_std::{sync::channel, task};
 use http_types::StatusCode;
 use log::{debug, warn, LevelFilter};
 use std::time::{Duration, Instant};
-use surf;
 use tokio::runtime::Runtime;
 use warp::Filter;
 
-use crate::Distribution::Percentile;
-use async_std::sync::Receiver;
-use clobber::{Job, JobStatus, PidController, WorkerPool, WorkerPoolCommand};
-use std::{
-    cmp::{max, Ordering::Equal},
-    collections::{HashMap, VecDeque},
-    fmt::{Debug, Formatter},
+use clobber::{
+    ConcurrencyController, GradientController, Job, JobStatus, PidController, Sample, WorkerPool,
+    WorkerPoolCommand,
 };
+use std::fmt::{Debug, Formatter};
 
 fn main() {
     start_logger(LevelFilter::Debug);
@@ -32,6 +29,8 @@ fn main() {
 
         let (send, recv) = channel(num_workers);
         let mut pid = PidController::new((0.1, 0.1, 0.1));
+        // caps the pool when the PID's worker count starts to push latency up
+        let mut gradient = GradientController::new(500).with_limits(1, 500);
         let mut pool = WorkerPool::new(load_url, send, num_workers);
         let mut overall_tracker = RequestTracker::new();
         let commands = pool.command_channel();
@@ -50,14 +49,19 @@ fn main() {
                 tick_tracker.add(metric);
 
                 if Instant::now() > next_tick {
-                    pid.update(goal_rps, tick_tracker.rps());
+                    pid.update_at(goal_rps, tick_tracker.rps(), Instant::now());
                     let mut new_worker_cnt = pid.output() * 0.001 * tick_rate.as_secs_f32();
                     if new_worker_cnt < 0.0 {
                         new_worker_cnt = 0.0;
                     }
 
-                    commands
-                        .send(WorkerPoolCommand::SetWorkerCount(new_worker_cnt.round() as usize));
+                    gradient.observe(tick_tracker.sample());
+                    let command = if gradient.recommend() < new_worker_cnt.round() as usize {
+                        gradient.command()
+                    } else {
+                        WorkerPoolCommand::SetWorkerCount(new_worker_cnt.round() as usize)
+                    };
+                    commands.send(command).ok();
 
                     debug!("{}, {}", new_worker_cnt, tick_tracker.rps());
 
@@ -78,7 +82,7 @@ fn main() {
         // Give each of our starting workers something to chew on. These last forever, so
         // in this case we just want one task per worker.
         for _ in 0..500 {
-            pool.push((&url, None));
+            pool.push((url, None));
         }
 
         pool.work().await;
@@ -101,25 +105,43 @@ struct Metric {
 struct RequestTracker {
     /// RequestTracker keeps track of the previous `size` requests
     count: usize,
+    errors: usize,
+    /// Summed request durations, for the mean latency
+    latency: Duration,
     start: Instant,
 }
 
 impl RequestTracker {
     pub fn new() -> Self {
-        Self { start: Instant::now(), count: 0 }
+        Self { start: Instant::now(), count: 0, errors: 0, latency: Duration::from_secs(0) }
     }
 
     pub fn count(&self) -> usize {
         self.count
     }
 
-    pub fn add(&mut self, _metric: Metric) {
+    pub fn add(&mut self, metric: Metric) {
         self.count += 1;
+        self.latency += metric.duration;
+        if !metric.result.is_success() {
+            self.errors += 1;
+        }
     }
 
     pub fn rps(&self) -> f32 {
         self.count() as f32 / Instant::now().duration_since(self.start).as_secs_f32()
     }
+
+    /// Everything tracked so far, for the latency-based controllers
+    pub fn sample(&self) -> Sample {
+        Sample {
+            elapsed: Instant::now().duration_since(self.start),
+            requests: self.count,
+            errors: self.errors,
+            latency: self.latency / self.count.max(1) as u32,
+            ..Sample::default()
+        }
+    }
 }
 
 impl Debug for RequestTracker {
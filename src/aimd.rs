@@ -0,0 +1,154 @@
+use crate::controller::{ConcurrencyController, Sample};
+use log::debug;
+use std::time::Duration;
+
+/// # AimdController
+///
+/// Additive-increase/multiplicative-decrease, the same strategy TCP uses to find out how
+/// much it can send at once. Every healthy interval adds a fixed number of workers, and
+/// every interval that sees errors, timeouts or latency over the threshold multiplies the
+/// worker count by a backoff factor.
+///
+/// The result is a sawtooth that hovers just under the point where the target starts to
+/// struggle. There are no gains to tune, which makes it a reasonable default when you
+/// don't want to think about control theory at all.
+///
+/// ```
+/// use clobber::{AimdController, ConcurrencyController, Sample};
+/// use std::time::Duration;
+///
+/// let mut aimd = AimdController::new(10).with_latency_threshold(Duration::from_millis(200));
+/// let healthy = Sample { requests: 100, latency: Duration::from_millis(20), ..Sample::default() };
+///
+/// aimd.observe(healthy);
+/// assert_eq!(aimd.recommend(), 11);
+///
+/// aimd.observe(Sample { errors: 1, ..healthy });
+/// assert_eq!(aimd.recommend(), 10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AimdController {
+    /// Current worker limit, kept fractional so repeated backoffs don't stall on rounding
+    limit: f32,
+    /// Workers added after a healthy interval
+    increase: f32,
+    /// Factor applied after an unhealthy interval, between 0 and 1
+    backoff: f32,
+    min_limit: usize,
+    max_limit: usize,
+    /// Latency above this counts as congestion
+    latency_threshold: Option<Duration>,
+}
+
+impl AimdController {
+    /// Creates a controller starting at `initial` workers that adds one worker per healthy
+    /// interval and backs off by 10% on trouble.
+    pub fn new(initial: usize) -> Self {
+        Self {
+            limit: initial as f32,
+            increase: 1.0,
+            backoff: 0.9,
+            min_limit: 1,
+            max_limit: usize::MAX,
+            latency_threshold: None,
+        }
+    }
+
+    /// Sets how many workers are added after each healthy interval.
+    pub fn with_increase(mut self, increase: f32) -> Self {
+        self.increase = increase;
+        self
+    }
+
+    /// Sets the factor the worker count is multiplied by after an unhealthy interval.
+    pub fn with_backoff(mut self, backoff: f32) -> Self {
+        assert!(backoff > 0.0 && backoff < 1.0, "backoff must be between 0 and 1");
+        self.backoff = backoff;
+        self
+    }
+
+    /// Bounds the recommendation to `[min, max]` workers.
+    pub fn with_limits(mut self, min: usize, max: usize) -> Self {
+        assert!(min <= max, "limits must satisfy min <= max");
+        self.min_limit = min;
+        self.max_limit = max;
+        self.limit = self.clamp(self.limit);
+        self
+    }
+
+    /// Treats intervals with latency above `threshold` as congested.
+    pub fn with_latency_threshold(mut self, threshold: Duration) -> Self {
+        self.latency_threshold = Some(threshold);
+        self
+    }
+
+    fn congested(&self, sample: &Sample) -> bool {
+        let slow = match self.latency_threshold {
+            Some(threshold) => sample.latency > threshold,
+            None => false,
+        };
+
+        sample.errors > 0 || slow
+    }
+
+    fn clamp(&self, limit: f32) -> f32 {
+        limit.max(self.min_limit as f32).min(self.max_limit as f32)
+    }
+}
+
+impl ConcurrencyController for AimdController {
+    fn observe(&mut self, sample: Sample) {
+        if self.congested(&sample) {
+            self.limit = self.clamp(self.limit * self.backoff);
+        } else if sample.requests > 0 {
+            // an idle interval tells us nothing about whether we could go faster
+            self.limit = self.clamp(self.limit + self.increase);
+        }
+
+        debug!("AimdController, {}", self.limit);
+    }
+
+    fn recommend(&self) -> usize {
+        self.limit.round() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sawtooth() {
+        let mut aimd = AimdController::new(10).with_backoff(0.5).with_limits(2, 12);
+        let healthy = Sample { requests: 10, ..Sample::default() };
+
+        for _ in 0..5 {
+            aimd.observe(healthy);
+        }
+        assert_eq!(aimd.recommend(), 12);
+
+        aimd.observe(Sample { errors: 3, ..healthy });
+        assert_eq!(aimd.recommend(), 6);
+
+        for _ in 0..5 {
+            aimd.observe(Sample { errors: 3, ..healthy });
+        }
+        assert_eq!(aimd.recommend(), 2);
+
+        // idle intervals hold steady
+        aimd.observe(Sample::default());
+        assert_eq!(aimd.recommend(), 2);
+    }
+
+    #[test]
+    fn slow_responses_count_as_congestion() {
+        let mut aimd = AimdController::new(10).with_latency_threshold(Duration::from_millis(100));
+
+        aimd.observe(Sample {
+            requests: 10,
+            latency: Duration::from_millis(150),
+            ..Sample::default()
+        });
+        assert_eq!(aimd.recommend(), 9);
+    }
+}
@@ -0,0 +1,224 @@
+use log::debug;
+use std::fmt;
+
+/// One tick of a closed-loop controller trace.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TracePoint {
+    /// Seconds since the trace started
+    pub time: f32,
+    pub setpoint: f32,
+    /// Process value, e.g. rps
+    pub value: f32,
+    /// Controller output, e.g. worker count
+    pub output: f32,
+}
+
+/// # Trace
+///
+/// A recorded closed-loop response, for judging a set of gains by the numbers instead of
+/// by eye.
+///
+/// The step metrics (rise time, overshoot, settling time) treat the trace as a single step
+/// from the first process value to the final setpoint, so record one setpoint change per
+/// trace. The error integrals follow the setpoint at every point and are meaningful for
+/// any trace.
+///
+/// Every point is logged at `debug` level as it's pushed, so a run can be read back with
+/// `tuning::read_trace`.
+///
+/// ```
+/// use clobber::{Trace, TracePoint};
+///
+/// let mut trace = Trace::new();
+/// for (time, value) in [(0.0, 0.0), (1.0, 60.0), (2.0, 95.0), (3.0, 104.0), (4.0, 100.0)].iter() {
+///     trace.push(TracePoint { time: *time, setpoint: 100.0, value: *value, output: 0.0 });
+/// }
+///
+/// let analysis = trace.analyze().unwrap();
+/// assert!((analysis.overshoot - 4.0).abs() < 1e-3);
+/// println!("{}", analysis);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    points: Vec<TracePoint>,
+    /// Fraction of the step size the process value must stay within to count as settled
+    settling_band: f32,
+}
+
+/// Performance figures for a closed-loop `Trace`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TraceAnalysis {
+    /// Seconds to go from 10% to 90% of the step, if the process value got that far
+    pub rise_time: Option<f32>,
+    /// Peak excursion past the final setpoint, as a percentage of the step size
+    pub overshoot: f32,
+    /// Seconds from the start until the process value stays within the settling band,
+    /// if it ever does
+    pub settling_time: Option<f32>,
+    /// Final setpoint minus the mean process value over the last 10% of the trace
+    pub steady_state_error: f32,
+    /// Integral of absolute error
+    pub iae: f32,
+    /// Integral of squared error. Punishes large errors, i.e. the start of a step.
+    pub ise: f32,
+    /// Integral of time-weighted absolute error. Punishes errors that linger.
+    pub itae: f32,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self { points: vec![], settling_band: 0.02 }
+    }
+
+    /// Sets the settling band as a fraction of the step size. Defaults to 2%.
+    pub fn with_settling_band(mut self, band: f32) -> Self {
+        assert!(band > 0.0, "settling band must be positive");
+        self.settling_band = band;
+        self
+    }
+
+    pub fn push(&mut self, point: TracePoint) {
+        debug!("Trace, {}, {}, {}, {}", point.time, point.setpoint, point.value, point.output);
+        self.points.push(point);
+    }
+
+    pub fn points(&self) -> &[TracePoint] {
+        &self.points
+    }
+
+    /// Computes performance figures for the trace, or `None` if there are fewer than two
+    /// points or the trace doesn't span any time.
+    pub fn analyze(&self) -> Option<TraceAnalysis> {
+        let first = self.points.first()?;
+        let last = self.points.last()?;
+        if self.points.len() < 2 || last.time <= first.time {
+            return None;
+        }
+
+        let start = first.time;
+        let initial = first.value;
+        let target = last.setpoint;
+        let step = target - initial;
+
+        // progress along the step, so a falling step reads the same as a rising one
+        let progress = |value: f32| if step == 0.0 { 1.0 } else { (value - initial) / step };
+        let crossing = |fraction: f32| {
+            self.points.iter().find(|p| progress(p.value) >= fraction).map(|p| p.time)
+        };
+        let rise_time = match (crossing(0.1), crossing(0.9)) {
+            (Some(low), Some(high)) => Some(high - low),
+            _ => None,
+        };
+
+        let peak = self.points.iter().map(|p| progress(p.value)).fold(f32::NEG_INFINITY, f32::max);
+        let overshoot = if step == 0.0 { 0.0 } else { ((peak - 1.0) * 100.0).max(0.0) };
+
+        let band =
+            self.settling_band * if step == 0.0 { target.abs().max(1.0) } else { step.abs() };
+        let settling_time = match self.points.iter().rposition(|p| (p.value - target).abs() > band)
+        {
+            None => Some(0.0),
+            Some(i) if i + 1 < self.points.len() => Some(self.points[i + 1].time - start),
+            Some(_) => None,
+        };
+
+        let tail_start = last.time - (last.time - start) * 0.1;
+        let tail = self
+            .points
+            .iter()
+            .filter(|p| p.time >= tail_start)
+            .map(|p| p.value)
+            .collect::<Vec<f32>>();
+        let steady_state_error = target - tail.iter().sum::<f32>() / tail.len() as f32;
+
+        // trapezoidal integration of the error against the setpoint in effect at each point
+        let (mut iae, mut ise, mut itae) = (0.0, 0.0, 0.0);
+        for pair in self.points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let dt = b.time - a.time;
+            let (ea, eb) = (a.setpoint - a.value, b.setpoint - b.value);
+            iae += (ea.abs() + eb.abs()) / 2.0 * dt;
+            ise += (ea * ea + eb * eb) / 2.0 * dt;
+            itae += ((a.time - start) * ea.abs() + (b.time - start) * eb.abs()) / 2.0 * dt;
+        }
+
+        Some(TraceAnalysis {
+            rise_time,
+            overshoot,
+            settling_time,
+            steady_state_error,
+            iae,
+            ise,
+            itae,
+        })
+    }
+}
+
+impl fmt::Display for TraceAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = |time: Option<f32>| match time {
+            Some(time) => format!("{:.3}s", time),
+            None => "never".to_string(),
+        };
+
+        writeln!(f, "rise time:          {}", seconds(self.rise_time))?;
+        writeln!(f, "overshoot:          {:.1}%", self.overshoot)?;
+        writeln!(f, "settling time:      {}", seconds(self.settling_time))?;
+        writeln!(f, "steady-state error: {}", self.steady_state_error)?;
+        write!(f, "IAE: {}, ISE: {}, ITAE: {}", self.iae, self.ise, self.itae)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(values: &[f32]) -> Trace {
+        let mut trace = Trace::new();
+        for (i, value) in values.iter().enumerate() {
+            trace.push(TracePoint { time: i as f32, setpoint: 10.0, value: *value, output: 0.0 });
+        }
+        trace
+    }
+
+    #[test]
+    fn step_metrics() {
+        let analysis =
+            trace(&[0.0, 5.0, 9.5, 12.0, 10.5, 10.1, 10.0, 10.0, 10.0, 10.0]).analyze().unwrap();
+
+        // 10% is crossed at t = 1, 90% at t = 2
+        assert_eq!(analysis.rise_time, Some(1.0));
+        assert!((analysis.overshoot - 20.0).abs() < 1e-3);
+        // 10.5 is outside the 2% band, 10.1 is inside
+        assert_eq!(analysis.settling_time, Some(5.0));
+        assert_eq!(analysis.steady_state_error, 0.0);
+    }
+
+    #[test]
+    fn error_integrals() {
+        // error of 10 for one second, then zero
+        let analysis = trace(&[0.0, 10.0, 10.0]).analyze().unwrap();
+        assert_eq!(analysis.iae, 5.0);
+        assert_eq!(analysis.ise, 50.0);
+        assert_eq!(analysis.itae, 0.0);
+
+        let analysis = trace(&[10.0, 0.0, 10.0]).analyze().unwrap();
+        assert_eq!(analysis.iae, 10.0);
+        assert_eq!(analysis.itae, 10.0);
+    }
+
+    #[test]
+    fn never_settles() {
+        let analysis = trace(&[0.0, 2.0, 4.0, 6.0]).analyze().unwrap();
+        assert_eq!(analysis.rise_time, None);
+        assert_eq!(analysis.settling_time, None);
+        assert_eq!(analysis.steady_state_error, 4.0);
+        assert_eq!(Trace::new().analyze(), None);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{
+    controller::{ConcurrencyController, ProcessVariable, Sample},
+    pid::PidController,
+};
+use log::debug;
+
+/// # CascadeController
+///
+/// Two `PidController`s chained so that the outer loop's output is the inner loop's goal.
+///
+/// The classic use is an outer loop holding p99 latency at a target by choosing how many
+/// requests per second to make, and an inner loop delivering that rps by choosing how many
+/// workers to run. The inner loop reacts quickly to throughput disturbances before they
+/// ever show up in latency, and the outer loop only has to think about latency.
+///
+/// When the inner loop is pinned at one of its output limits it can't deliver what the
+/// outer loop asks for. Left alone, the outer loop would keep integrating that shortfall
+/// and wind up. Instead, while the inner loop is saturated in the direction the outer
+/// loop is pushing, the outer loop tracks what the inner loop actually achieves, so it
+/// resumes from reality once the inner loop has room again.
+///
+/// ```
+/// use clobber::{CascadeController, PidController, ProcessVariable};
+///
+/// // hold latency at 200ms by adjusting the rps goal, then hit that rps with 1 to 500 workers
+/// let outer = PidController::new((2000.0, 500.0, 0.0)).with_goal(0.2).with_output_limits(0.0, 50_000.0);
+/// let inner = PidController::new((0.01, 0.05, 0.0)).with_output_limits(1.0, 500.0);
+///
+/// let controller =
+///     CascadeController::new(outer, ProcessVariable::Latency, inner, ProcessVariable::Throughput);
+/// ```
+pub struct CascadeController {
+    outer: PidController,
+    outer_variable: ProcessVariable,
+    inner: PidController,
+    inner_variable: ProcessVariable,
+}
+
+impl CascadeController {
+    /// Chains `outer`, which controls `outer_variable`, into `inner`, which controls
+    /// `inner_variable` and whose output is the recommendation. The outer controller keeps
+    /// its own goal; the inner controller's goal is overwritten every update.
+    pub fn new(
+        outer: PidController,
+        outer_variable: ProcessVariable,
+        inner: PidController,
+        inner_variable: ProcessVariable,
+    ) -> Self {
+        Self { outer, outer_variable, inner, inner_variable }
+    }
+
+    pub fn outer(&self) -> &PidController {
+        &self.outer
+    }
+
+    pub fn outer_mut(&mut self) -> &mut PidController {
+        &mut self.outer
+    }
+
+    pub fn inner(&self) -> &PidController {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut PidController {
+        &mut self.inner
+    }
+}
+
+impl ConcurrencyController for CascadeController {
+    fn observe(&mut self, sample: Sample) {
+        let outer_value = self.outer_variable.read(&sample);
+        let inner_value = self.inner_variable.read(&sample);
+
+        self.outer.update_with_dt(self.outer.goal(), outer_value, sample.elapsed);
+
+        // Only track when the outer loop wants to push further into the limit; if it's
+        // asking the inner loop to back off, let it.
+        let saturation = self.inner.saturation();
+        let demand = self.outer.output() - inner_value;
+        if (saturation > 0.0 && demand > 0.0) || (saturation < 0.0 && demand < 0.0) {
+            self.outer.track(inner_value);
+        }
+
+        self.inner.update_with_dt(self.outer.output(), inner_value, sample.elapsed);
+
+        debug!("CascadeController, {}, {}", self.outer.output(), self.inner.output());
+    }
+
+    fn recommend(&self) -> usize {
+        self.inner.recommend()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outer_output_drives_inner_goal() {
+        let outer = PidController::new((1000.0, 0.0, 0.0)).with_goal(0.2);
+        let inner = PidController::new((0.1, 0.0, 0.0));
+        let mut controller = CascadeController::new(
+            outer,
+            ProcessVariable::Latency,
+            inner,
+            ProcessVariable::Throughput,
+        );
+
+        // 100ms under the latency goal sets an rps goal of 100, and at 50 rps the inner
+        // loop makes up the other 50
+        controller.observe(Sample::one_second(50, 100));
+        assert!((controller.inner().goal() - 100.0).abs() < 1e-3);
+        assert_eq!(controller.recommend(), 5);
+    }
+
+    #[test]
+    fn inner_saturation_does_not_wind_up_outer() {
+        let outer = PidController::new((0.0, 1000.0, 0.0)).with_goal(0.2);
+        let inner = PidController::new((0.0, 1.0, 0.0)).with_output_limits(0.0, 10.0);
+        let mut controller = CascadeController::new(
+            outer,
+            ProcessVariable::Latency,
+            inner,
+            ProcessVariable::Throughput,
+        );
+
+        // latency is comfortably low, but the inner loop tops out at 50 rps
+        for _ in 0..100 {
+            controller.observe(Sample::one_second(50, 100));
+        }
+
+        assert_eq!(controller.recommend(), 10);
+        assert!(controller.outer().output() < 200.0);
+    }
+}
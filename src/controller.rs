@@ -0,0 +1,178 @@
+#[cfg(feature = "std")]
+use crate::pool::WorkerPoolCommand;
+use core::time::Duration;
+
+/// A summary of how the workload behaved over one control interval.
+///
+/// This is the common currency between a measurement source and any
+/// `ConcurrencyController`. Not every controller looks at every field; a throughput
+/// controller ignores latency, a latency controller ignores throughput, but keeping them
+/// together means the control loop doesn't have to change when the algorithm does.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Sample {
+    /// Length of the interval this sample covers.
+    pub elapsed: Duration,
+    /// Requests completed during the interval, including failures.
+    pub requests: usize,
+    /// Requests that failed or timed out during the interval.
+    pub errors: usize,
+    /// Representative request latency for the interval, e.g. the mean or p99.
+    pub latency: Duration,
+    /// Workers that were running while the sample was collected.
+    pub workers: usize,
+    /// Requests per second, for sources that produce a rate rather than a count, such as
+    /// `Filtered`. Takes precedence over `requests / elapsed` in `throughput`.
+    pub rate: Option<f32>,
+}
+
+impl Sample {
+    /// Completed requests per second over the interval.
+    pub fn throughput(&self) -> f32 {
+        if let Some(rate) = self.rate {
+            return rate;
+        }
+
+        let secs = self.elapsed.as_secs_f32();
+        if secs > 0.0 {
+            self.requests as f32 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Fraction of requests in the interval that failed, between 0 and 1.
+    pub fn error_rate(&self) -> f32 {
+        if self.requests > 0 {
+            self.errors as f32 / self.requests as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+impl Sample {
+    /// One second's worth of `requests` at `latency_ms`, the fixture most controller tests
+    /// need.
+    pub(crate) fn one_second(requests: usize, latency_ms: u64) -> Self {
+        Sample {
+            elapsed: Duration::from_secs(1),
+            requests,
+            latency: Duration::from_millis(latency_ms),
+            ..Sample::default()
+        }
+    }
+}
+
+/// A signal that can be read out of a `Sample`, for controllers that can be pointed at
+/// different measurements.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ProcessVariable {
+    /// `Sample::throughput`, in requests per second
+    Throughput,
+    /// `Sample::latency`, in seconds
+    Latency,
+    /// `Sample::error_rate`, between 0 and 1
+    ErrorRate,
+    /// `Sample::workers`
+    Workers,
+}
+
+impl ProcessVariable {
+    pub fn read(&self, sample: &Sample) -> f32 {
+        match self {
+            ProcessVariable::Throughput => sample.throughput(),
+            ProcessVariable::Latency => sample.latency.as_secs_f32(),
+            ProcessVariable::ErrorRate => sample.error_rate(),
+            ProcessVariable::Workers => sample.workers as f32,
+        }
+    }
+}
+
+/// # ConcurrencyController
+///
+/// Anything that can answer "how many workers?" given a stream of observations.
+///
+/// A control loop feeds each interval's `Sample` to `observe`, then asks `recommend` for
+/// the worker count to use next. Because every algorithm speaks this interface, the loop
+/// that drives a `WorkerPool` can stay the same while the algorithm behind it changes.
+///
+/// ```
+/// use clobber::{ConcurrencyController, PidController, Sample};
+/// use std::time::Duration;
+///
+/// let mut controller = PidController::new((0.01, 0.0, 0.0)).with_goal(1000.0);
+/// controller.observe(Sample {
+///     elapsed: Duration::from_secs(1),
+///     requests: 200,
+///     ..Sample::default()
+/// });
+///
+/// assert_eq!(controller.recommend(), 8);
+/// ```
+pub trait ConcurrencyController {
+    /// Feeds the controller the latest observation.
+    fn observe(&mut self, sample: Sample);
+
+    /// The number of workers the controller currently recommends.
+    fn recommend(&self) -> usize;
+
+    /// Wraps the recommendation in a command ready for `WorkerPool::command_channel`.
+    #[cfg(feature = "std")]
+    fn command(&self) -> WorkerPoolCommand {
+        WorkerPoolCommand::SetWorkerCount(self.recommend())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<C: ConcurrencyController + ?Sized> ConcurrencyController for Box<C> {
+    fn observe(&mut self, sample: Sample) {
+        (**self).observe(sample)
+    }
+
+    fn recommend(&self) -> usize {
+        (**self).recommend()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PidController;
+
+    #[test]
+    fn sample_rates() {
+        let sample = Sample {
+            elapsed: Duration::from_millis(500),
+            requests: 50,
+            errors: 5,
+            ..Sample::default()
+        };
+
+        assert_eq!(sample.throughput(), 100.0);
+        assert_eq!(sample.error_rate(), 0.1);
+        assert_eq!(Sample::default().throughput(), 0.0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn controllers_are_swappable() {
+        let mut controllers: Vec<Box<dyn ConcurrencyController>> = vec![
+            Box::new(PidController::new((0.1, 0.0, 0.0)).with_goal(100.0)),
+            Box::new(PidController::new((-1.0, 0.0, 0.0)).with_goal(100.0)),
+        ];
+
+        let sample = Sample { elapsed: Duration::from_secs(1), requests: 50, ..Sample::default() };
+        for controller in controllers.iter_mut() {
+            controller.observe(sample);
+        }
+
+        // negative output never turns into a negative worker count
+        assert_eq!(controllers[0].recommend(), 5);
+        assert_eq!(controllers[1].recommend(), 0);
+        match controllers[0].command() {
+            WorkerPoolCommand::SetWorkerCount(n) => assert_eq!(n, 5),
+            command => panic!("unexpected command {:?}", command),
+        }
+    }
+}
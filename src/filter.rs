@@ -0,0 +1,352 @@
+//! # Measurement Filters
+//!
+//! A 100ms tick doesn't leave much room for the law of large numbers, and raw rps readings
+//! bounce around enough to make any controller nervous. These filters sit between a
+//! measurement source and a controller to smooth that out.
+//!
+//! Every filter trades noise for lag; a smoother signal is an older signal, and a
+//! controller acting on old information overshoots. Each filter documents its lag in
+//! samples, so multiply by the tick rate to see how far behind the controller will be.
+//!
+//! Filters compose with `then`:
+//! ```
+//! use clobber::{Ewma, Filter, SlidingMedian};
+//!
+//! // knock out single-tick spikes, then smooth what's left
+//! let mut filter = SlidingMedian::new(3).then(Ewma::new(0.3));
+//! for rps in [100.0, 104.0, 5000.0, 98.0, 101.0].iter() {
+//!     filter.filter(*rps);
+//! }
+//! assert!(filter.value().unwrap() < 110.0);
+//! ```
+
+use crate::controller::{ConcurrencyController, ProcessVariable, Sample};
+use std::{collections::VecDeque, time::Duration};
+
+/// A signal filter that turns a stream of raw measurements into a smoothed one.
+pub trait Filter {
+    /// Feeds in the next measurement and returns the filtered value.
+    fn filter(&mut self, value: f32) -> f32;
+
+    /// The latest filtered value, or `None` before the first measurement.
+    fn value(&self) -> Option<f32>;
+
+    /// Feeds this filter's output into `next`.
+    fn then<B: Filter>(self, next: B) -> Chain<Self, B>
+    where
+        Self: Sized,
+    {
+        Chain { first: self, second: next }
+    }
+}
+
+/// Two filters in series. Lag is the sum of both filters' lag.
+#[derive(Debug, Clone)]
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Filter, B: Filter> Filter for Chain<A, B> {
+    fn filter(&mut self, value: f32) -> f32 {
+        let value = self.first.filter(value);
+        self.second.filter(value)
+    }
+
+    fn value(&self) -> Option<f32> {
+        self.second.value()
+    }
+}
+
+/// Exponentially weighted moving average.
+///
+/// Each output moves `alpha` of the way towards the new measurement. Cheap and smooth,
+/// but a single outlier still pulls it off course. Lags a ramp by `(1 - alpha) / alpha`
+/// samples; an `alpha` of 0.2 is 4 samples, or 400ms at a 100ms tick.
+#[derive(Debug, Clone)]
+pub struct Ewma {
+    alpha: f32,
+    value: Option<f32>,
+}
+
+impl Ewma {
+    /// Creates a filter with smoothing factor `alpha` in `(0, 1]`. Lower is smoother.
+    pub fn new(alpha: f32) -> Self {
+        assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be in (0, 1]");
+        Self { alpha, value: None }
+    }
+
+    /// Creates a filter with roughly the same lag as an `n` sample moving average.
+    pub fn with_window(n: usize) -> Self {
+        Self::new(2.0 / (n.max(1) as f32 + 1.0))
+    }
+}
+
+impl Filter for Ewma {
+    fn filter(&mut self, value: f32) -> f32 {
+        let filtered = match self.value {
+            Some(last) => last + self.alpha * (value - last),
+            None => value,
+        };
+        self.value = Some(filtered);
+        filtered
+    }
+
+    fn value(&self) -> Option<f32> {
+        self.value
+    }
+}
+
+/// Simple moving average over the last `window` measurements.
+///
+/// Every measurement in the window counts equally, and each drops out abruptly once it's
+/// `window` samples old. Lags a ramp by `(window - 1) / 2` samples.
+#[derive(Debug, Clone)]
+pub struct MovingAverage {
+    window: usize,
+    values: VecDeque<f32>,
+    sum: f32,
+}
+
+impl MovingAverage {
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        Self { window, values: VecDeque::with_capacity(window), sum: 0.0 }
+    }
+}
+
+impl Filter for MovingAverage {
+    fn filter(&mut self, value: f32) -> f32 {
+        self.values.push_back(value);
+        self.sum += value;
+        if self.values.len() > self.window {
+            self.sum -= self.values.pop_front().unwrap_or_default();
+        }
+
+        self.sum / self.values.len() as f32
+    }
+
+    fn value(&self) -> Option<f32> {
+        if self.values.is_empty() {
+            None
+        } else {
+            Some(self.sum / self.values.len() as f32)
+        }
+    }
+}
+
+/// Median of the last `window` measurements.
+///
+/// Ignores spikes entirely as long as they last fewer than half the window, which makes
+/// it the right first stage when a measurement source occasionally reports garbage. Lags
+/// a ramp by `(window - 1) / 2` samples.
+#[derive(Debug, Clone)]
+pub struct SlidingMedian {
+    window: usize,
+    values: VecDeque<f32>,
+}
+
+impl SlidingMedian {
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        Self { window, values: VecDeque::with_capacity(window) }
+    }
+}
+
+impl Filter for SlidingMedian {
+    fn filter(&mut self, value: f32) -> f32 {
+        self.values.push_back(value);
+        if self.values.len() > self.window {
+            self.values.pop_front();
+        }
+
+        self.value().unwrap_or(value)
+    }
+
+    fn value(&self) -> Option<f32> {
+        if self.values.is_empty() {
+            return None;
+        }
+
+        let mut sorted = self.values.iter().copied().collect::<Vec<f32>>();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mid = sorted.len() / 2;
+        if sorted.len() % 2 == 0 {
+            Some((sorted[mid - 1] + sorted[mid]) / 2.0)
+        } else {
+            Some(sorted[mid])
+        }
+    }
+}
+
+/// One-dimensional Kalman filter for a value that drifts as a random walk.
+///
+/// `process_noise` is how much the true value is expected to move between samples, and
+/// `measurement_noise` is the variance of the measurement error. Their ratio is all that
+/// matters: the filter settles into an `Ewma` whose alpha is the steady-state Kalman
+/// gain, but it starts out trusting measurements heavily and converges quickly, and the
+/// gain comes from noise figures you can estimate instead of being picked by hand.
+#[derive(Debug, Clone)]
+pub struct Kalman {
+    process_noise: f32,
+    measurement_noise: f32,
+    estimate: Option<f32>,
+    /// Variance of the current estimate
+    variance: f32,
+}
+
+impl Kalman {
+    pub fn new(process_noise: f32, measurement_noise: f32) -> Self {
+        assert!(measurement_noise > 0.0, "measurement noise must be positive");
+        Self { process_noise, measurement_noise, estimate: None, variance: measurement_noise }
+    }
+
+    /// The current Kalman gain: how far the next estimate moves towards the next
+    /// measurement. Equivalent to `Ewma`'s alpha, with the same lag of
+    /// `(1 - gain) / gain` samples.
+    pub fn gain(&self) -> f32 {
+        let predicted = self.variance + self.process_noise;
+        predicted / (predicted + self.measurement_noise)
+    }
+}
+
+impl Filter for Kalman {
+    fn filter(&mut self, value: f32) -> f32 {
+        let estimate = match self.estimate {
+            Some(estimate) => {
+                let gain = self.gain();
+                self.variance = (1.0 - gain) * (self.variance + self.process_noise);
+                estimate + gain * (value - estimate)
+            }
+            None => value,
+        };
+        self.estimate = Some(estimate);
+        estimate
+    }
+
+    fn value(&self) -> Option<f32> {
+        self.estimate
+    }
+}
+
+/// # Filtered
+///
+/// Runs one `ProcessVariable` of every `Sample` through a `Filter` before the wrapped
+/// controller sees it.
+///
+/// The filtered value is written back into the sample the controller sees. Throughput
+/// goes into `Sample::rate` exactly, but error counts are whole numbers, so a filtered
+/// error rate is only as fine as one error in `requests`.
+///
+/// ```
+/// use clobber::{Ewma, Filtered, PidController, ProcessVariable};
+///
+/// let pid = PidController::new((0.01, 0.01, 0.0)).with_goal(1000.0);
+/// let controller = Filtered::new(pid, ProcessVariable::Throughput, Ewma::new(0.3));
+/// ```
+pub struct Filtered<C, F> {
+    inner: C,
+    variable: ProcessVariable,
+    filter: F,
+}
+
+impl<C: ConcurrencyController, F: Filter> Filtered<C, F> {
+    pub fn new(inner: C, variable: ProcessVariable, filter: F) -> Self {
+        Self { inner, variable, filter }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+}
+
+impl<C: ConcurrencyController, F: Filter> ConcurrencyController for Filtered<C, F> {
+    fn observe(&mut self, mut sample: Sample) {
+        let value = self.filter.filter(self.variable.read(&sample)).max(0.0);
+
+        match self.variable {
+            ProcessVariable::Throughput => sample.rate = Some(value),
+            ProcessVariable::Latency => sample.latency = Duration::from_secs_f32(value),
+            ProcessVariable::ErrorRate => {
+                sample.errors = (value * sample.requests as f32).round() as usize;
+            }
+            ProcessVariable::Workers => sample.workers = value.round() as usize,
+        }
+
+        self.inner.observe(sample);
+    }
+
+    fn recommend(&self) -> usize {
+        self.inner.recommend()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PidController;
+
+    #[test]
+    fn smoothing() {
+        let mut ewma = Ewma::new(0.5);
+        assert_eq!(ewma.value(), None);
+        assert_eq!(ewma.filter(10.0), 10.0);
+        assert_eq!(ewma.filter(20.0), 15.0);
+
+        let mut average = MovingAverage::new(2);
+        average.filter(10.0);
+        average.filter(20.0);
+        assert_eq!(average.filter(40.0), 30.0);
+
+        let mut median = SlidingMedian::new(3);
+        median.filter(10.0);
+        median.filter(1000.0);
+        assert_eq!(median.filter(12.0), 12.0);
+    }
+
+    #[test]
+    fn kalman_converges_to_steady_gain() {
+        let mut kalman = Kalman::new(1.0, 100.0);
+        for _ in 0..100 {
+            kalman.filter(50.0);
+        }
+
+        // steady state for q = 1, r = 100 is roughly 0.095
+        assert!((kalman.gain() - 0.095).abs() < 0.005);
+        assert!((kalman.value().unwrap() - 50.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn filtered_throughput_keeps_its_resolution() {
+        let pid = PidController::new((1.0, 0.0, 0.0)).with_goal(100.0);
+        let mut controller = Filtered::new(pid, ProcessVariable::Throughput, MovingAverage::new(2));
+        let tick = Sample { elapsed: Duration::from_millis(100), ..Sample::default() };
+
+        // 10 and 20 rps average to 15, which isn't a whole number of requests per 100ms
+        controller.observe(Sample { requests: 1, ..tick });
+        controller.observe(Sample { requests: 2, ..tick });
+        assert_eq!(controller.recommend(), 85);
+    }
+
+    #[test]
+    fn filtered_controller_sees_smoothed_samples() {
+        let pid = PidController::new((1.0, 0.0, 0.0)).with_goal(100.0);
+        let mut controller = Filtered::new(pid, ProcessVariable::Throughput, MovingAverage::new(2));
+        let tick = Sample { elapsed: Duration::from_secs(1), ..Sample::default() };
+
+        controller.observe(Sample { requests: 40, ..tick });
+        controller.observe(Sample { requests: 80, ..tick });
+
+        // the controller saw 60 rps, not 80
+        assert_eq!(controller.recommend(), 40);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::controller::{ConcurrencyController, Sample};
+use log::debug;
+
+/// # GradientController
+///
+/// A latency-driven limiter in the style of Netflix's Gradient2.
+///
+/// It keeps two views of round trip time: a long-term baseline, tracked as an exponential
+/// moving average over many samples, and the short-term latency of the latest interval.
+/// Their ratio is the gradient. While latency sits at the baseline the gradient is 1 and
+/// the limit creeps upwards by a small queue allowance; when latency climbs above the
+/// baseline the gradient drops below 1 and the limit shrinks in proportion.
+///
+/// This is the README's "add latency and the right worker count shifts" scenario handled
+/// automatically: the target slows down, the gradient falls, and the pool sheds workers.
+///
+/// ```
+/// use clobber::{ConcurrencyController, GradientController, Sample};
+/// use std::time::Duration;
+///
+/// let mut gradient = GradientController::new(20);
+/// let sample = Sample { requests: 100, latency: Duration::from_millis(10), ..Sample::default() };
+///
+/// for _ in 0..10 {
+///     gradient.observe(sample);
+/// }
+/// let settled = gradient.recommend();
+///
+/// // the target slows down to three times its usual latency
+/// gradient.observe(Sample { latency: Duration::from_millis(30), ..sample });
+/// assert!(gradient.recommend() < settled);
+/// ```
+#[derive(Debug, Clone)]
+pub struct GradientController {
+    limit: f32,
+    min_limit: usize,
+    max_limit: usize,
+    /// Long-term RTT baseline in seconds, `None` until the first sample arrives
+    long_rtt: Option<f32>,
+    /// Number of samples the long-term average spans
+    long_window: usize,
+    /// How much short-term latency may exceed the baseline before the limit shrinks
+    tolerance: f32,
+    /// Weight given to each new limit estimate, between 0 and 1
+    smoothing: f32,
+}
+
+impl GradientController {
+    /// Creates a controller starting at `initial` workers with Gradient2's defaults: a
+    /// 600 sample baseline, a tolerance of 1.5 and 20% smoothing.
+    pub fn new(initial: usize) -> Self {
+        Self {
+            limit: initial as f32,
+            min_limit: 1,
+            max_limit: usize::MAX,
+            long_rtt: None,
+            long_window: 600,
+            tolerance: 1.5,
+            smoothing: 0.2,
+        }
+    }
+
+    /// Bounds the recommendation to `[min, max]` workers.
+    pub fn with_limits(mut self, min: usize, max: usize) -> Self {
+        assert!(min <= max, "limits must satisfy min <= max");
+        self.min_limit = min;
+        self.max_limit = max;
+        self.limit = self.clamp(self.limit);
+        self
+    }
+
+    /// Sets how many samples the long-term RTT baseline averages over.
+    pub fn with_long_window(mut self, samples: usize) -> Self {
+        self.long_window = samples.max(1);
+        self
+    }
+
+    /// Sets how far short-term latency may rise above the baseline, as a ratio, before the
+    /// limit starts to shrink.
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        assert!(tolerance >= 1.0, "tolerance must be at least 1");
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Sets the weight given to each new estimate. Lower values react more slowly.
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        assert!(smoothing > 0.0 && smoothing <= 1.0, "smoothing must be in (0, 1]");
+        self.smoothing = smoothing;
+        self
+    }
+
+    fn clamp(&self, limit: f32) -> f32 {
+        limit.max(self.min_limit as f32).min(self.max_limit as f32)
+    }
+}
+
+impl ConcurrencyController for GradientController {
+    fn observe(&mut self, sample: Sample) {
+        let short_rtt = sample.latency.as_secs_f32();
+        if sample.requests == 0 || short_rtt <= 0.0 {
+            return;
+        }
+
+        let alpha = 2.0 / (self.long_window as f32 + 1.0);
+        let mut long_rtt = match self.long_rtt {
+            Some(long_rtt) => long_rtt + alpha * (short_rtt - long_rtt),
+            None => short_rtt,
+        };
+
+        // If the baseline has drifted well above what we're seeing now, the target has
+        // recovered from a slow period; decay quickly rather than waiting out the window.
+        if long_rtt / short_rtt > 2.0 {
+            long_rtt *= 0.95;
+        }
+        self.long_rtt = Some(long_rtt);
+
+        let gradient = (self.tolerance * long_rtt / short_rtt).clamp(0.5, 1.0);
+        let queue_size = self.limit.sqrt();
+        let estimate = self.limit * gradient + queue_size;
+        self.limit = self.clamp(self.limit * (1.0 - self.smoothing) + estimate * self.smoothing);
+
+        debug!("GradientController, {}, {}, {}", short_rtt, long_rtt, self.limit);
+    }
+
+    fn recommend(&self) -> usize {
+        self.limit.round() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn sample(latency_ms: u64) -> Sample {
+        Sample { requests: 10, latency: Duration::from_millis(latency_ms), ..Sample::default() }
+    }
+
+    #[test]
+    fn grows_while_latency_is_steady() {
+        let mut gradient = GradientController::new(10).with_limits(1, 50);
+
+        for _ in 0..200 {
+            gradient.observe(sample(10));
+        }
+
+        assert_eq!(gradient.recommend(), 50);
+    }
+
+    #[test]
+    fn sheds_workers_when_latency_rises() {
+        let mut gradient = GradientController::new(100).with_limits(1, 100);
+        for _ in 0..50 {
+            gradient.observe(sample(10));
+        }
+
+        for _ in 0..20 {
+            gradient.observe(sample(100));
+        }
+
+        assert!(gradient.recommend() < 30);
+    }
+
+    #[test]
+    fn ignores_idle_intervals() {
+        let mut gradient = GradientController::new(10);
+        gradient.observe(Sample::default());
+
+        assert_eq!(gradient.recommend(), 10);
+    }
+}
@@ -1,11 +1,82 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+//! With `default-features = false`, only the PID core and the `ConcurrencyController`
+//! trait are built, and they only need `core`. Everything else requires the `std` feature,
+//! which is on by default.
+
+#[cfg(feature = "std")]
+mod aimd;
+#[cfg(feature = "std")]
+mod analysis;
+#[cfg(feature = "std")]
+mod cascade;
+mod controller;
+#[cfg(feature = "std")]
+mod filter;
+#[cfg(feature = "std")]
+mod gradient;
+#[cfg(feature = "std")]
+mod mpc;
+#[cfg(feature = "std")]
+mod optimize;
 mod pid;
+#[cfg(feature = "std")]
 mod pool;
+#[cfg(feature = "std")]
+mod relay;
+#[cfg(feature = "std")]
+mod schedule;
+#[cfg(feature = "std")]
+mod selector;
+#[cfg(feature = "std")]
+mod setpoint;
+#[cfg(feature = "std")]
+mod shaping;
+#[cfg(feature = "std")]
+mod step;
+#[cfg(feature = "std")]
+mod vegas;
 
+#[cfg(feature = "std")]
+pub mod sim;
 #[cfg(feature = "tuning")]
 pub mod tuning;
 
-pub use pid::PidController;
+#[cfg(feature = "std")]
+pub use aimd::AimdController;
+#[cfg(feature = "std")]
+pub use analysis::{Trace, TraceAnalysis, TracePoint};
+#[cfg(feature = "std")]
+pub use cascade::CascadeController;
+pub use controller::{ConcurrencyController, ProcessVariable, Sample};
+#[cfg(feature = "std")]
+pub use filter::{Chain, Ewma, Filter, Filtered, Kalman, MovingAverage, SlidingMedian};
+#[cfg(feature = "std")]
+pub use gradient::GradientController;
+#[cfg(feature = "std")]
+pub use mpc::MpcController;
+#[cfg(feature = "std")]
+pub use optimize::{GainOptimizer, TuningCost, TuningReport};
+pub use pid::{
+    littles_law, AntiWindup, ControlMode, DerivativeFilter, DerivativeSource, Feedforward,
+    PidController, PidSnapshot, Real, TermSnapshot,
+};
+#[cfg(feature = "std")]
 pub use pool::{Job, JobStatus, WorkerPool, WorkerPoolCommand};
+#[cfg(feature = "std")]
+pub use relay::{RelayResult, RelayTuner, RelayTuningRule};
+#[cfg(feature = "std")]
+pub use schedule::{GainSchedule, GainScheduledController, ScheduleVariable};
+#[cfg(feature = "std")]
+pub use selector::SelectorController;
+#[cfg(feature = "std")]
+pub use setpoint::{ProfileSetpoint, RampedSetpoint, Setpoint, SetpointFollower};
+#[cfg(feature = "std")]
+pub use shaping::{Hysteresis, SlewRateLimit};
+#[cfg(feature = "std")]
+pub use step::{FopdtModel, StepPoint, StepResponse, StepTest, StepTuningRule};
+#[cfg(feature = "std")]
+pub use vegas::VegasController;
 
 #[cfg(test)]
 mod tests {
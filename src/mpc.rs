@@ -0,0 +1,303 @@
+use crate::{
+    controller::{ConcurrencyController, ProcessVariable, Sample},
+    sim::{FopdtPlant, Plant},
+    FopdtModel,
+};
+use log::debug;
+use std::time::Duration;
+
+/// # MpcController
+///
+/// A model predictive controller: rather than reacting to the error it sees now, it uses
+/// a process model to predict where the process value is heading and picks the worker
+/// count that lands closest to the goal over the next `horizon`.
+///
+/// This matters when the target has significant dead time. A PID loop keeps pushing until
+/// it sees a response, by which point it has pushed too far. The MPC controller knows that
+/// the workers it has already added haven't shown up in the measurements yet, and waits
+/// for them.
+///
+/// The model is a `FopdtModel`, typically one identified with a `StepTest`. Each tick the
+/// controller:
+///
+/// 1. Advances its copy of the model with the workers that actually ran, and treats the
+///    gap between the model and the measurement as a constant disturbance. This keeps
+///    it on target when the model's gain is off. The workers that ran come from
+///    `Sample::workers`; a sample that leaves it at zero is assumed to have run the
+///    previous recommendation.
+/// 2. Predicts the process value over the horizon for a worker count held from now on,
+///    and picks the count that minimises the squared error to the goal plus a penalty on
+///    the size of the move.
+/// 3. Clamps that to the worker limits and to how far the rate limits let it move in one
+///    tick.
+///
+/// Holding a single move over the horizon keeps the optimisation exact and cheap (it's a
+/// one-dimensional quadratic), at the cost of not being able to plan a ramp in advance.
+///
+/// ```
+/// use clobber::{ConcurrencyController, FopdtModel, MpcController};
+/// use std::time::Duration;
+///
+/// // each worker adds 20 rps, after a 2s delay and with a 1s time constant
+/// let model = FopdtModel {
+///     gain: 20.0,
+///     time_constant: Duration::from_secs(1),
+///     dead_time: Duration::from_secs(2),
+/// };
+///
+/// let mpc = MpcController::new(model, 0).with_goal(1000.0).with_limits(0, 100);
+/// assert_eq!(mpc.recommend(), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MpcController {
+    model: FopdtModel,
+    /// The model's idea of the process, fed the same workers as the real one
+    predictor: FopdtPlant,
+    goal: f32,
+    variable: ProcessVariable,
+    /// How far ahead to predict. Defaults to the dead time plus two time constants.
+    horizon: Duration,
+    /// Weight on the squared change in workers, relative to the squared tracking error
+    move_penalty: f32,
+    min_workers: usize,
+    max_workers: usize,
+    /// Most workers to add per second
+    max_increase: f32,
+    /// Most workers to remove per second
+    max_decrease: f32,
+    /// Measured minus modelled process value
+    disturbance: f32,
+    /// Recommended workers, kept fractional so slow rate limits still make progress
+    workers: f32,
+}
+
+impl MpcController {
+    /// Creates a controller for a process described by `model`, currently running
+    /// `initial` workers and assumed to have settled.
+    pub fn new(model: FopdtModel, initial: usize) -> Self {
+        Self {
+            model,
+            predictor: FopdtPlant::new(model).with_initial_input(initial as f32),
+            goal: 0.0,
+            variable: ProcessVariable::Throughput,
+            horizon: model.dead_time + model.time_constant * 2,
+            move_penalty: 0.0,
+            min_workers: 0,
+            max_workers: usize::MAX,
+            max_increase: f32::INFINITY,
+            max_decrease: f32::INFINITY,
+            disturbance: 0.0,
+            workers: initial as f32,
+        }
+    }
+
+    pub fn with_goal(mut self, goal: f32) -> Self {
+        self.goal = goal;
+        self
+    }
+
+    /// Sets which measurement the model predicts. Defaults to throughput.
+    pub fn with_process_variable(mut self, variable: ProcessVariable) -> Self {
+        self.variable = variable;
+        self
+    }
+
+    /// Sets how far ahead to predict. Should cover at least the dead time, or the
+    /// controller can't see the effect of anything it does.
+    pub fn with_horizon(mut self, horizon: Duration) -> Self {
+        self.horizon = horizon;
+        self
+    }
+
+    /// Penalises changes in the worker count, trading speed for calm. The penalty is on
+    /// workers squared, against the tracking error in process units squared, so scale it
+    /// with the model gain.
+    pub fn with_move_penalty(mut self, penalty: f32) -> Self {
+        assert!(penalty >= 0.0, "move penalty can't be negative");
+        self.move_penalty = penalty;
+        self
+    }
+
+    /// Bounds the recommendation to `[min, max]` workers.
+    pub fn with_limits(mut self, min: usize, max: usize) -> Self {
+        assert!(min <= max, "limits must satisfy min <= max");
+        self.min_workers = min;
+        self.max_workers = max;
+        self.workers = self.workers.clamp(min as f32, max as f32);
+        self
+    }
+
+    /// Caps how many workers per second can be added (`up`) or removed (`down`).
+    pub fn with_rate_limits(mut self, up: f32, down: f32) -> Self {
+        assert!(up > 0.0 && down > 0.0, "rate limits must be positive");
+        self.max_increase = up;
+        self.max_decrease = down;
+        self
+    }
+
+    pub fn goal(&self) -> f32 {
+        self.goal
+    }
+
+    pub fn set_goal(&mut self, goal: f32) {
+        self.goal = goal;
+    }
+
+    pub fn model(&self) -> &FopdtModel {
+        &self.model
+    }
+
+    /// The unconstrained best worker count to hold for the rest of the horizon.
+    fn optimize(&self, dt: Duration) -> f32 {
+        let steps = (self.horizon.as_secs_f32() / dt.as_secs_f32()).ceil().max(1.0) as usize;
+
+        // the prediction is linear in the held worker count: free response plus count
+        // times the response to a single worker
+        let mut free = self.predictor.clone();
+        let mut forced = self.predictor.clone();
+        let (mut numerator, mut denominator) = (0.0, 0.0);
+        for _ in 0..steps {
+            let unforced = free.step(0.0, dt);
+            let per_worker = forced.step(1.0, dt) - unforced;
+            let error = self.goal - self.disturbance - unforced;
+            numerator += per_worker * error;
+            denominator += per_worker * per_worker;
+        }
+
+        numerator += self.move_penalty * self.workers;
+        denominator += self.move_penalty;
+        if denominator > 0.0 {
+            numerator / denominator
+        } else {
+            self.workers
+        }
+    }
+}
+
+impl ConcurrencyController for MpcController {
+    fn observe(&mut self, sample: Sample) {
+        let dt = sample.elapsed;
+        if dt == Duration::from_secs(0) {
+            return;
+        }
+
+        // most samples never fill in `workers`, so fall back to what we asked for
+        let running = if sample.workers > 0 { sample.workers as f32 } else { self.workers };
+        let measured = self.variable.read(&sample);
+        let modelled = self.predictor.step(running, dt);
+        self.disturbance = measured - modelled;
+
+        let secs = dt.as_secs_f32();
+        let low = (self.workers - self.max_decrease * secs).max(self.min_workers as f32);
+        let high = (self.workers + self.max_increase * secs).min(self.max_workers as f32);
+        self.workers = self.optimize(dt).max(low).min(high);
+
+        debug!("MpcController, {}, {}, {}", measured, self.disturbance, self.workers);
+    }
+
+    fn recommend(&self) -> usize {
+        self.workers.round() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Trace, TracePoint};
+
+    const TICK: Duration = Duration::from_millis(100);
+
+    fn model() -> FopdtModel {
+        FopdtModel {
+            gain: 20.0,
+            time_constant: Duration::from_secs(1),
+            dead_time: Duration::from_secs(2),
+        }
+    }
+
+    /// Runs `mpc` against `plant` for `ticks` ticks and records the response.
+    fn run(mpc: &mut MpcController, plant: &mut FopdtPlant, ticks: usize) -> Trace {
+        run_reporting(mpc, plant, ticks, true)
+    }
+
+    /// Like `run`, optionally leaving `Sample::workers` unset the way most sources do.
+    fn run_reporting(
+        mpc: &mut MpcController,
+        plant: &mut FopdtPlant,
+        ticks: usize,
+        report_workers: bool,
+    ) -> Trace {
+        let mut trace = Trace::new();
+        for tick in 0..ticks {
+            let workers = mpc.recommend();
+            let value = plant.step(workers as f32, TICK);
+            mpc.observe(Sample {
+                elapsed: TICK,
+                requests: (value * TICK.as_secs_f32()).round() as usize,
+                workers: if report_workers { workers } else { 0 },
+                ..Sample::default()
+            });
+            trace.push(TracePoint {
+                time: tick as f32 * 0.1,
+                setpoint: mpc.goal(),
+                value,
+                output: workers as f32,
+            });
+        }
+        trace
+    }
+
+    #[test]
+    fn waits_out_the_dead_time() {
+        let mut mpc = MpcController::new(model(), 0).with_goal(1000.0);
+        let mut plant = FopdtPlant::new(model());
+
+        let analysis = run(&mut mpc, &mut plant, 150).analyze().unwrap();
+        assert_eq!(mpc.recommend(), 50);
+        assert!(analysis.overshoot < 5.0);
+        assert!(analysis.steady_state_error.abs() < 10.0);
+    }
+
+    #[test]
+    fn assumes_its_recommendation_ran_without_worker_counts() {
+        let mut mpc = MpcController::new(model(), 0).with_goal(1000.0);
+        let mut plant = FopdtPlant::new(model());
+
+        let analysis = run_reporting(&mut mpc, &mut plant, 150, false).analyze().unwrap();
+        assert_eq!(mpc.recommend(), 50);
+        assert!(analysis.steady_state_error.abs() < 10.0);
+    }
+
+    #[test]
+    fn corrects_for_model_error() {
+        // the real target only manages 15 rps per worker
+        let mut mpc = MpcController::new(model(), 0).with_goal(900.0);
+        let mut plant = FopdtPlant::new(FopdtModel { gain: 15.0, ..model() });
+
+        let analysis = run(&mut mpc, &mut plant, 400).analyze().unwrap();
+        assert_eq!(mpc.recommend(), 60);
+        assert!(analysis.steady_state_error.abs() < 10.0);
+    }
+
+    #[test]
+    fn respects_limits() {
+        let mut mpc = MpcController::new(model(), 0)
+            .with_goal(1000.0)
+            .with_limits(0, 40)
+            .with_rate_limits(10.0, 5.0);
+        let mut plant = FopdtPlant::new(model());
+
+        let trace = run(&mut mpc, &mut plant, 100);
+        for pair in trace.points().windows(2) {
+            assert!(pair[1].output - pair[0].output <= 1.0);
+            assert!(pair[1].output <= 40.0);
+        }
+        assert_eq!(mpc.recommend(), 40);
+
+        // a lower goal sheds workers at no more than 5 per second
+        mpc.set_goal(0.0);
+        let trace = run(&mut mpc, &mut plant, 10);
+        assert!(trace.points().windows(2).all(|pair| pair[0].output - pair[1].output <= 1.0));
+        assert_eq!(mpc.recommend(), 35);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::{
+    sim::{simulate, Plant},
+    PidController, Trace, TraceAnalysis,
+};
+use std::{fmt, time::Duration};
+
+/// Weights for scoring a closed-loop run. Lower scores are better.
+///
+/// ```txt
+/// cost = itae * ITAE + overshoot * overshoot% + churn * Σ|Δoutput|
+/// ```
+///
+/// ITAE alone rewards getting to the setpoint fast and staying there. The overshoot
+/// penalty trades some of that speed for a gentler approach, and the churn penalty
+/// punishes outputs that bounce around, which on a worker pool means constantly
+/// starting and stopping workers.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TuningCost {
+    pub itae: f32,
+    pub overshoot: f32,
+    pub churn: f32,
+}
+
+impl Default for TuningCost {
+    fn default() -> Self {
+        Self { itae: 1.0, overshoot: 0.0, churn: 0.0 }
+    }
+}
+
+impl TuningCost {
+    /// Scores a trace, or returns infinity if it can't be analyzed or blew up.
+    pub fn score(&self, trace: &Trace) -> f32 {
+        let analysis = match trace.analyze() {
+            Some(analysis) => analysis,
+            None => return f32::INFINITY,
+        };
+
+        let churn = trace
+            .points()
+            .windows(2)
+            .map(|pair| (pair[1].output - pair[0].output).abs())
+            .sum::<f32>();
+        let cost =
+            self.itae * analysis.itae + self.overshoot * analysis.overshoot + self.churn * churn;
+
+        if cost.is_finite() {
+            cost
+        } else {
+            f32::INFINITY
+        }
+    }
+}
+
+/// The outcome of a `GainOptimizer` run.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TuningReport {
+    /// The best `(p, i, d)` found, ready for `PidController::new`
+    pub gains: (f32, f32, f32),
+    pub cost: f32,
+    /// How the best gains performed
+    pub analysis: TraceAnalysis,
+    /// The best gains on the grid, before refinement
+    pub grid_gains: (f32, f32, f32),
+    pub grid_cost: f32,
+    /// Simulated runs it took to get here
+    pub evaluations: usize,
+}
+
+impl fmt::Display for TuningReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (p, i, d) = self.gains;
+        writeln!(f, "gains:              ({}, {}, {})", p, i, d)?;
+        writeln!(f, "cost:               {}", self.cost)?;
+        let (p, i, d) = self.grid_gains;
+        writeln!(f, "grid search:        ({}, {}, {}) at {}", p, i, d, self.grid_cost)?;
+        writeln!(f, "evaluations:        {}", self.evaluations)?;
+        write!(f, "{}", self.analysis)
+    }
+}
+
+/// # GainOptimizer
+///
+/// Searches for PID gains against a simulated plant, in place of the hand-tuning loop of
+/// running a load test, squinting at the chart and trying again.
+///
+/// The search runs in two stages. A coarse grid search over the gain ranges finds the
+/// right neighbourhood, then Nelder–Mead refines the best grid point. Every candidate is
+/// scored by simulating a step to the setpoint from rest with `sim::simulate` and
+/// weighing the resulting trace with a `TuningCost`.
+///
+/// The plant is built fresh for every candidate by the factory passed to `new`, so a
+/// noisy plant should be seeded to keep the comparison fair.
+///
+/// ```
+/// use clobber::sim::FopdtPlant;
+/// use clobber::{FopdtModel, GainOptimizer, PidController};
+/// use std::time::Duration;
+///
+/// let model = FopdtModel {
+///     gain: 50.0,
+///     time_constant: Duration::from_secs(2),
+///     dead_time: Duration::from_millis(300),
+/// };
+///
+/// let report = GainOptimizer::new(|| FopdtPlant::new(model), 1000.0)
+///     .with_ranges((0.0, 0.1), (0.0, 0.1), (0.0, 0.0))
+///     .optimize();
+///
+/// println!("{}", report);
+/// let pid = PidController::new(report.gains);
+/// ```
+pub struct GainOptimizer<F> {
+    plant: F,
+    setpoint: f32,
+    tick: Duration,
+    ticks: usize,
+    cost: TuningCost,
+    /// (min, max) for each of p, i and d
+    ranges: [(f32, f32); 3],
+    /// Points per axis in the grid search
+    grid_steps: usize,
+    iterations: usize,
+    output_limits: Option<(f32, f32)>,
+}
+
+impl<P: Plant, F: Fn() -> P> GainOptimizer<F> {
+    /// Creates an optimizer that steps `plant()` from rest to `setpoint`.
+    pub fn new(plant: F, setpoint: f32) -> Self {
+        Self {
+            plant,
+            setpoint,
+            tick: Duration::from_millis(100),
+            ticks: 300,
+            cost: TuningCost::default(),
+            ranges: [(0.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+            grid_steps: 5,
+            iterations: 200,
+            output_limits: None,
+        }
+    }
+
+    /// Sets the tick length and how many ticks each simulated run lasts. Defaults to 300
+    /// ticks of 100ms.
+    pub fn with_duration(mut self, tick: Duration, ticks: usize) -> Self {
+        assert!(ticks > 1, "a run needs at least two ticks");
+        self.tick = tick;
+        self.ticks = ticks;
+        self
+    }
+
+    pub fn with_cost(mut self, cost: TuningCost) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Sets the `(min, max)` range searched for each gain. A range with equal bounds holds
+    /// that gain fixed. Defaults to 0 to 1 for p and i, and no derivative.
+    pub fn with_ranges(mut self, p: (f32, f32), i: (f32, f32), d: (f32, f32)) -> Self {
+        for (min, max) in [p, i, d].iter() {
+            assert!(min <= max, "gain range minimum must not exceed the maximum");
+        }
+        self.ranges = [p, i, d];
+        self
+    }
+
+    /// Sets how many evenly spaced points per gain the grid search tries. Defaults to 5.
+    pub fn with_grid_steps(mut self, steps: usize) -> Self {
+        assert!(steps > 1, "a grid needs at least two points per axis");
+        self.grid_steps = steps;
+        self
+    }
+
+    /// Caps the number of Nelder–Mead iterations. Defaults to 200.
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Applies output limits to every candidate controller, e.g. the pool's worker
+    /// bounds.
+    pub fn with_output_limits(mut self, min: f32, max: f32) -> Self {
+        self.output_limits = Some((min, max));
+        self
+    }
+
+    /// Simulates a run with `gains` and returns its trace.
+    pub fn simulate(&self, gains: (f32, f32, f32)) -> Trace {
+        let mut pid = PidController::new(gains);
+        if let Some((min, max)) = self.output_limits {
+            pid = pid.with_output_limits(min, max);
+        }
+
+        simulate(&mut pid, &mut (self.plant)(), self.setpoint, self.tick, self.ticks)
+    }
+
+    /// Runs the grid search and refinement, and reports the best gains found.
+    pub fn optimize(&self) -> TuningReport {
+        let mut evaluations = 0;
+        let mut evaluate = |gains: (f32, f32, f32)| {
+            evaluations += 1;
+            self.cost.score(&self.simulate(gains))
+        };
+
+        // only gains with a range to search are part of the simplex
+        let free = (0..3)
+            .filter(|&axis| self.ranges[axis].0 < self.ranges[axis].1)
+            .collect::<Vec<usize>>();
+        let gains_at = |point: &[f32]| {
+            let mut gains = [self.ranges[0].0, self.ranges[1].0, self.ranges[2].0];
+            for (&axis, &value) in free.iter().zip(point) {
+                let (min, max) = self.ranges[axis];
+                gains[axis] = value.clamp(min, max);
+            }
+            (gains[0], gains[1], gains[2])
+        };
+        let grid_value = |axis: usize, step: usize| {
+            let (min, max) = self.ranges[axis];
+            min + (max - min) * step as f32 / (self.grid_steps - 1) as f32
+        };
+
+        // grid search
+        let mut grid_best = (vec![0.0; free.len()], f32::INFINITY);
+        let points = self.grid_steps.pow(free.len() as u32);
+        for index in 0..points {
+            let point = free
+                .iter()
+                .enumerate()
+                .map(|(n, &axis)| {
+                    grid_value(axis, index / self.grid_steps.pow(n as u32) % self.grid_steps)
+                })
+                .collect::<Vec<f32>>();
+            let cost = evaluate(gains_at(&point));
+            if cost < grid_best.1 {
+                grid_best = (point, cost);
+            }
+        }
+
+        // Nelder–Mead, starting from a simplex one grid step wide around the grid winner
+        let mut simplex = vec![grid_best.clone()];
+        for (n, &axis) in free.iter().enumerate() {
+            let (min, max) = self.ranges[axis];
+            let step = (max - min) / (self.grid_steps - 1) as f32;
+            let mut point = grid_best.0.clone();
+            point[n] = if point[n] + step <= max { point[n] + step } else { point[n] - step };
+            let cost = evaluate(gains_at(&point));
+            simplex.push((point, cost));
+        }
+
+        for _ in 0..if free.is_empty() { 0 } else { self.iterations } {
+            simplex.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+            let (best, worst) = (simplex[0].1, simplex[free.len()].1);
+            if (worst - best).abs() <= 1e-6 * best.abs().max(1e-6) {
+                break;
+            }
+
+            let centroid = (0..free.len())
+                .map(|n| {
+                    simplex[..free.len()].iter().map(|(point, _)| point[n]).sum::<f32>()
+                        / free.len() as f32
+                })
+                .collect::<Vec<f32>>();
+            let towards = |scale: f32, point: &[f32]| {
+                centroid.iter().zip(point).map(|(c, p)| c + scale * (p - c)).collect::<Vec<f32>>()
+            };
+
+            let worst_point = simplex[free.len()].0.clone();
+            let reflected = towards(-1.0, &worst_point);
+            let reflected_cost = evaluate(gains_at(&reflected));
+
+            let replacement = if reflected_cost < best {
+                let expanded = towards(-2.0, &worst_point);
+                let expanded_cost = evaluate(gains_at(&expanded));
+                if expanded_cost < reflected_cost {
+                    Some((expanded, expanded_cost))
+                } else {
+                    Some((reflected, reflected_cost))
+                }
+            } else if reflected_cost < simplex[free.len() - 1].1 {
+                Some((reflected, reflected_cost))
+            } else {
+                let contracted = towards(0.5, &worst_point);
+                let contracted_cost = evaluate(gains_at(&contracted));
+                if contracted_cost < simplex[free.len()].1 {
+                    Some((contracted, contracted_cost))
+                } else {
+                    None
+                }
+            };
+
+            match replacement {
+                Some(vertex) => simplex[free.len()] = vertex,
+                None => {
+                    // shrink everything towards the best vertex
+                    let best_point = simplex[0].0.clone();
+                    for vertex in simplex.iter_mut().skip(1) {
+                        let point = best_point
+                            .iter()
+                            .zip(&vertex.0)
+                            .map(|(b, p)| b + 0.5 * (p - b))
+                            .collect::<Vec<f32>>();
+                        let cost = evaluate(gains_at(&point));
+                        *vertex = (point, cost);
+                    }
+                }
+            }
+        }
+
+        let (point, cost) = simplex
+            .into_iter()
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap_or_else(|| grid_best.clone());
+        let gains = gains_at(&point);
+        let grid_gains = gains_at(&grid_best.0);
+
+        // re-run the winner for its analysis, which is only missing if every run failed
+        let analysis = self.simulate(gains).analyze().unwrap_or(TraceAnalysis {
+            rise_time: None,
+            overshoot: 0.0,
+            settling_time: None,
+            steady_state_error: f32::INFINITY,
+            iae: f32::INFINITY,
+            ise: f32::INFINITY,
+            itae: f32::INFINITY,
+        });
+
+        TuningReport { gains, cost, analysis, grid_gains, grid_cost: grid_best.1, evaluations }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sim::FopdtPlant, FopdtModel, TracePoint};
+
+    fn model() -> FopdtModel {
+        FopdtModel {
+            gain: 10.0,
+            time_constant: Duration::from_secs(2),
+            dead_time: Duration::from_millis(300),
+        }
+    }
+
+    #[test]
+    fn refines_the_grid() {
+        let report = GainOptimizer::new(|| FopdtPlant::new(model()), 100.0)
+            .with_ranges((0.0, 1.0), (0.0, 1.0), (0.0, 0.0))
+            .optimize();
+
+        assert!(report.cost <= report.grid_cost);
+        assert!(report.evaluations > 25);
+        assert_eq!(report.gains.2, 0.0);
+        assert!(report.analysis.steady_state_error.abs() < 1.0);
+
+        // better than a reasonable hand-picked guess
+        let guess = TuningCost::default().score(
+            &GainOptimizer::new(|| FopdtPlant::new(model()), 100.0).simulate((0.1, 0.1, 0.0)),
+        );
+        assert!(report.cost < guess);
+    }
+
+    #[test]
+    fn churn_penalty_calms_the_output() {
+        let churn = |cost: TuningCost| {
+            let optimizer =
+                GainOptimizer::new(|| FopdtPlant::new(model()).with_noise(2.0, 9), 100.0)
+                    .with_ranges((0.0, 1.0), (0.0, 1.0), (0.0, 0.0))
+                    .with_cost(cost);
+            let trace = optimizer.simulate(optimizer.optimize().gains);
+            trace
+                .points()
+                .windows(2)
+                .map(|pair| (pair[1].output - pair[0].output).abs())
+                .sum::<f32>()
+        };
+
+        let fast = churn(TuningCost::default());
+        let calm = churn(TuningCost { churn: 10.0, ..TuningCost::default() });
+        assert!(calm < fast);
+    }
+
+    #[test]
+    fn cost_weights() {
+        let mut trace = Trace::new();
+        for (time, value, output) in [(0.0, 0.0, 0.0), (1.0, 12.0, 4.0), (2.0, 10.0, 2.0)].iter() {
+            trace.push(TracePoint { time: *time, setpoint: 10.0, value: *value, output: *output });
+        }
+
+        let cost = TuningCost { itae: 0.0, overshoot: 1.0, churn: 1.0 };
+        assert!((cost.score(&trace) - 26.0).abs() < 1e-3);
+        assert_eq!(TuningCost::default().score(&Trace::new()), f32::INFINITY);
+    }
+}
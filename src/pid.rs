@@ -1,4 +1,28 @@
+use crate::controller::{ConcurrencyController, Sample};
+use core::{fmt, time::Duration};
 use log::debug;
+use num_traits::Float;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Floating point types the PID core can run on; in practice `f32` or `f64`.
+/// Long integrations lose precision quickly in `f32`, so reach for `f64` when a controller
+/// runs for days.
+pub trait Real: Float + fmt::Debug + fmt::Display {}
+
+impl<T: Float + fmt::Debug + fmt::Display> Real for T {}
+
+/// Converts a `Duration` into seconds of type `F`.
+fn seconds<F: Real>(duration: Duration) -> F {
+    F::from(duration.as_secs_f64()).unwrap_or_else(F::max_value)
+}
+
+/// Converts an `f32` from the rest of the crate into `F`.
+fn real<F: Real>(value: f32) -> F {
+    F::from(value).unwrap_or_else(F::nan)
+}
 
 #[derive(Debug)]
 enum ControllerType {
@@ -7,61 +31,767 @@ enum ControllerType {
     Derivative,
 }
 
-struct Controller {
+struct Controller<F> {
     pub controller_type: ControllerType,
-    pub gain: f32,
-    pub error: f32,
+    pub gain: F,
+    pub error: F,
+    /// Raw error from the previous update, used to take the derivative.
+    pub last_error: Option<F>,
+    /// Time constant in seconds of the first-order low-pass filter applied to the
+    /// derivative. Zero disables filtering.
+    pub time_constant: F,
 }
 
-impl Controller {
-    pub fn new(controller_type: ControllerType, gain: f32) -> Self {
-        Self { controller_type, gain, error: 0.0 }
+impl<F: Real> Controller<F> {
+    pub fn new(controller_type: ControllerType, gain: F) -> Self {
+        Self { controller_type, gain, error: F::zero(), last_error: None, time_constant: F::zero() }
     }
 
-    pub fn update(&mut self, error: f32) {
+    /// Updates the controller with the latest error and the time in seconds since the
+    /// previous update.
+    pub fn update(&mut self, error: F, dt: F) {
         self.error = match self.controller_type {
             ControllerType::Proportional => error,
-            ControllerType::Integral => (error + self.error) / 2.0,
-            ControllerType::Derivative => error - self.error,
+            ControllerType::Integral => self.error + error * dt,
+            ControllerType::Derivative => match self.last_error {
+                Some(last_error) if dt > F::zero() => {
+                    let rate = (error - last_error) / dt;
+                    let alpha = dt / (self.time_constant + dt);
+                    self.error + alpha * (rate - self.error)
+                }
+                // no elapsed time means nothing new to say about the rate; keep the last
+                // one, and the history it should be measured from
+                Some(_) => return,
+                // no history means no rate of change to report
+                None => F::zero(),
+            },
         };
+        self.last_error = Some(error);
 
         debug!("{:#?}, {}", self.controller_type, self.error);
     }
 
-    pub fn output(&self) -> f32 {
+    pub fn output(&self) -> F {
         self.error * self.gain
     }
+
+    pub fn snapshot(&self) -> TermSnapshot<F> {
+        TermSnapshot { gain: self.gain, value: self.error, last_error: self.last_error }
+    }
+
+    pub fn restore(&mut self, snapshot: &TermSnapshot<F>) {
+        self.gain = snapshot.gain;
+        self.error = snapshot.value;
+        self.last_error = snapshot.last_error;
+    }
+}
+
+/// Saved state of one of the P, I or D terms.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TermSnapshot<F = f32> {
+    pub gain: F,
+    /// The term's value before gain: the error, its integral, or its derivative
+    pub value: F,
+    /// Error from the previous update
+    pub last_error: Option<F>,
+}
+
+/// Saved gains and accumulated state of a `PidController`.
+///
+/// Configuration set through the `with_*` builders, such as output limits or the
+/// feedforward function, is not part of the snapshot; restore into a controller built
+/// with the same configuration. Enable the `serde` feature to serialize snapshots.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PidSnapshot<F = f32> {
+    pub p: TermSnapshot<F>,
+    pub i: TermSnapshot<F>,
+    pub d: TermSnapshot<F>,
+    pub goal: F,
+    pub mode: ControlMode,
+    pub manual_output: F,
+    pub feedforward: F,
+}
+
+/// Strategy used to keep the integral term from winding up while the output is pinned
+/// against one of its limits.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AntiWindup<F = f32> {
+    /// Always integrate, even while saturated.
+    None,
+    /// Stop integrating while saturated if the error would push the output further past
+    /// the limit.
+    ConditionalIntegration,
+    /// Bleed the integral back towards the limit, proportional to how far past the limit
+    /// the unclamped output is. Larger `tracking_gain` unwinds faster.
+    BackCalculation { tracking_gain: F },
+}
+
+/// The signal the derivative term differentiates.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DerivativeSource {
+    /// Differentiate `goal - current`. Any step in `goal` shows up as a derivative kick.
+    Error,
+    /// Differentiate `-current` instead. Identical to `Error` while the goal holds still,
+    /// but ignores goal changes entirely.
+    Measurement,
+}
+
+/// First-order low-pass filter applied to the derivative term to tame noisy measurements.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DerivativeFilter<F = f32> {
+    /// Use the raw derivative.
+    None,
+    /// Filter with a fixed time constant.
+    TimeConstant(Duration),
+    /// Filter with a time constant of `Td / N`, where `Td = Kd / Kp` is the derivative
+    /// time. Typical values of `N` are between 2 and 20; lower filters harder.
+    N(F),
+}
+
+/// Whether the controller is computing its own output or following an operator.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ControlMode {
+    /// The PID terms drive the output.
+    Automatic,
+    /// The output is pinned by hand. The controller keeps observing so it can take over
+    /// again without a bump.
+    Manual,
+}
+
+/// Estimates the output needed to reach `goal` from what the latest `Sample` says about the
+/// process, ahead of any feedback.
+pub type Feedforward<F = f32> = fn(goal: F, sample: &Sample) -> F;
+
+/// Little's law feedforward for throughput goals: the workers needed to sustain `goal`
+/// requests per second is `goal` times the time each request takes.
+pub fn littles_law<F: Real>(goal: F, sample: &Sample) -> F {
+    goal * seconds(sample.latency)
 }
 
-pub struct PidController {
-    p: Controller,
-    i: Controller,
-    d: Controller,
+/// # PidController
+///
+/// Generic over the floating point type `F`, which defaults to `f32`. The core only needs
+/// `core`, so with `default-features = false` it builds for `no_std` targets; everything
+/// that needs a clock or an allocator, like `update_at`, stays behind the `std` feature.
+///
+/// ```
+/// use clobber::PidController;
+///
+/// let mut pid: PidController<f64> = PidController::new((0.5, 0.1, 0.0));
+/// pid.update(100.0, 20.0);
+/// ```
+pub struct PidController<F = f32> {
+    p: Controller<F>,
+    i: Controller<F>,
+    d: Controller<F>,
+    #[cfg(feature = "std")]
+    last_update: Option<Instant>,
+    output_limits: (F, F),
+    anti_windup: AntiWindup<F>,
+    derivative_source: DerivativeSource,
+    derivative_filter: DerivativeFilter<F>,
+    goal: F,
+    setpoint_weight: F,
+    mode: ControlMode,
+    manual_output: F,
+    /// Added to the output so the PID terms only have to correct the residual
+    feedforward: F,
+    feedforward_fn: Option<Feedforward<F>>,
+    deadband: F,
 }
 
-impl PidController {
+impl<F: Real> PidController<F> {
     /// Creates a new PidController with the provided `gain` tuple.
     /// Gain is used to balance the respective volume of each controller.
-    pub fn new(gain: (f32, f32, f32)) -> Self {
+    pub fn new(gain: (F, F, F)) -> Self {
         let (p_gain, i_gain, d_gain) = gain;
         Self {
             p: Controller::new(ControllerType::Proportional, p_gain),
             i: Controller::new(ControllerType::Integral, i_gain),
             d: Controller::new(ControllerType::Derivative, d_gain),
+            #[cfg(feature = "std")]
+            last_update: None,
+            output_limits: (F::neg_infinity(), F::infinity()),
+            anti_windup: AntiWindup::None,
+            derivative_source: DerivativeSource::Error,
+            derivative_filter: DerivativeFilter::None,
+            goal: F::zero(),
+            setpoint_weight: F::one(),
+            mode: ControlMode::Automatic,
+            manual_output: F::zero(),
+            feedforward: F::zero(),
+            feedforward_fn: None,
+            deadband: F::zero(),
+        }
+    }
+
+    /// Sets the goal used when the controller is driven through `ConcurrencyController`.
+    pub fn with_goal(mut self, goal: F) -> Self {
+        self.goal = goal;
+        self
+    }
+
+    /// Clamps `output` to `[min, max]`. Useful when the actuator has hard bounds, such as
+    /// a minimum and maximum worker count.
+    pub fn with_output_limits(mut self, min: F, max: F) -> Self {
+        assert!(min <= max, "output limits must satisfy min <= max");
+        self.output_limits = (min, max);
+        self
+    }
+
+    /// Sets how the integral term behaves while the output is saturated.
+    pub fn with_anti_windup(mut self, anti_windup: AntiWindup<F>) -> Self {
+        self.anti_windup = anti_windup;
+        self
+    }
+
+    /// Recomputes the feedforward term from every `Sample` the controller observes through
+    /// `ConcurrencyController`.
+    ///
+    /// ```
+    /// use clobber::{littles_law, PidController};
+    ///
+    /// let pid = PidController::new((0.001, 0.001, 0.0)).with_goal(1000.0).with_feedforward(littles_law);
+    /// ```
+    pub fn with_feedforward(mut self, feedforward: Feedforward<F>) -> Self {
+        self.feedforward_fn = Some(feedforward);
+        self
+    }
+
+    /// Sets a value that is added straight to the output, ahead of the PID terms. When it's
+    /// a good estimate of the output needed, the PID terms only have to correct the
+    /// residual error, which converges much faster than feedback alone.
+    pub fn set_feedforward(&mut self, value: F) {
+        self.feedforward = value;
+    }
+
+    pub fn feedforward(&self) -> F {
+        self.feedforward
+    }
+
+    /// Weights the goal in the proportional term, which then sees `weight * goal - current`
+    /// instead of the full error. Values below 1 soften the proportional kick from a goal
+    /// change without slowing down disturbance rejection; the integral still drives the
+    /// output all the way to the goal.
+    pub fn with_setpoint_weight(mut self, weight: F) -> Self {
+        self.setpoint_weight = weight;
+        self
+    }
+
+    /// Treats any error within `width` of the goal as no error at all, so the P and I terms
+    /// sit still while the process value wanders around the goal. Outside the band they
+    /// see the error less `width`, so the output grows smoothly from the band's edge
+    /// instead of jumping by `Kp * width` as noise crosses it. The derivative still sees the
+    /// raw signal.
+    pub fn with_deadband(mut self, width: F) -> Self {
+        self.deadband = width.abs();
+        self
+    }
+
+    /// Sets which signal the derivative term differentiates.
+    pub fn with_derivative_source(mut self, derivative_source: DerivativeSource) -> Self {
+        self.derivative_source = derivative_source;
+        self
+    }
+
+    /// Sets the low-pass filter applied to the derivative term.
+    pub fn with_derivative_filter(mut self, derivative_filter: DerivativeFilter<F>) -> Self {
+        self.derivative_filter = derivative_filter;
+        self
+    }
+
+    /// The current `(p, i, d)` gains.
+    pub fn gains(&self) -> (F, F, F) {
+        (self.p.gain, self.i.gain, self.d.gain)
+    }
+
+    /// Replaces the gains while the controller is running. The integral is back-calculated
+    /// so that the output carries on from where it was, whichever gains changed. Without
+    /// an integral gain there's nothing to absorb the difference, so expect a bump.
+    pub fn set_gains(&mut self, gain: (F, F, F)) {
+        let before = self.raw_output();
+
+        let (p_gain, i_gain, d_gain) = gain;
+        self.p.gain = p_gain;
+        self.i.gain = i_gain;
+        self.d.gain = d_gain;
+
+        if self.i.gain != F::zero() {
+            self.i.error =
+                (before - self.p.output() - self.d.output() - self.feedforward) / self.i.gain;
         }
     }
 
-    pub fn update(&mut self, goal: f32, current: f32) {
+    /// The most recent goal, from either `set_goal` or an update.
+    pub fn goal(&self) -> F {
+        self.goal
+    }
+
+    /// Changes the goal used when the controller is driven through `ConcurrencyController`.
+    /// Calls to `update` and friends also overwrite it.
+    pub fn set_goal(&mut self, goal: F) {
+        self.goal = goal;
+    }
+
+    pub fn mode(&self) -> ControlMode {
+        self.mode
+    }
+
+    /// Switches to manual mode and pins `output` to `value`, e.g. to hold the worker count
+    /// steady during an incident. Updates keep flowing into the P and D terms while the
+    /// integral is back-calculated to match, so the controller is always ready to take
+    /// over from exactly this value.
+    pub fn set_manual(&mut self, value: F) {
+        self.mode = ControlMode::Manual;
+        self.manual_output = value;
+        self.track(value);
+    }
+
+    /// Hands control back to the PID terms. Because the integral has been tracking the
+    /// manual output, the output carries on from the manual value instead of jumping.
+    /// Without an integral gain there's nothing to absorb the difference, so expect a bump.
+    pub fn set_automatic(&mut self) {
+        if self.mode == ControlMode::Manual {
+            self.track(self.manual_output);
+        }
+        self.mode = ControlMode::Automatic;
+    }
+
+    /// Updates the controller assuming a fixed tick; every call counts as one unit of time.
+    /// Prefer `update_with_dt` or `update_at` when ticks are irregular.
+    pub fn update(&mut self, goal: F, current: F) {
+        self.step(goal, current, F::one());
+    }
+
+    /// Updates the controller with the time elapsed since the previous update.
+    /// The integral accumulates `error * dt` and the derivative divides by `dt`, so gains
+    /// are expressed per second regardless of tick jitter.
+    pub fn update_with_dt(&mut self, goal: F, current: F, dt: Duration) {
+        self.step(goal, current, seconds(dt));
+    }
+
+    /// Updates the controller with a timestamped measurement, deriving `dt` from the
+    /// previous call. The first call has no elapsed time to work with, so it only seeds
+    /// the proportional term and the derivative history.
+    #[cfg(feature = "std")]
+    pub fn update_at(&mut self, goal: F, current: F, now: Instant) {
+        let dt = match self.last_update {
+            Some(last) => now.saturating_duration_since(last),
+            None => Duration::from_secs(0),
+        };
+        self.last_update = Some(now);
+
+        self.update_with_dt(goal, current, dt);
+    }
+
+    fn step(&mut self, goal: F, current: F, dt: F) {
+        self.goal = goal;
         let error = goal - current;
+        let in_deadband = error.abs() <= self.deadband;
+        // shrinking by the band keeps both terms continuous across its edge
+        let shrink = self.deadband * error.signum();
+
+        let p_error =
+            if in_deadband { F::zero() } else { self.setpoint_weight * goal - current - shrink };
+        self.p.update(p_error, dt);
+
+        self.d.time_constant = self.derivative_time_constant();
+        match self.derivative_source {
+            DerivativeSource::Error => self.d.update(error, dt),
+            DerivativeSource::Measurement => self.d.update(-current, dt),
+        }
 
-        self.p.update(error);
-        self.i.update(error);
-        self.d.update(error);
+        let integral = self.i.error;
+        self.i.update(if in_deadband { F::zero() } else { error - shrink }, dt);
+
+        match self.mode {
+            ControlMode::Manual => self.track(self.manual_output),
+            ControlMode::Automatic => self.limit_windup(error, integral, dt),
+        }
 
         debug!("PidController, {}", self.output());
     }
 
-    pub fn output(&self) -> f32 {
-        self.p.output() + self.i.output() + self.d.output()
+    /// Back-calculates the integral so that the output equals `value`, for when something
+    /// downstream is overriding this controller and it should be ready to pick up from
+    /// whatever is actually happening.
+    pub fn track(&mut self, value: F) {
+        if self.i.gain != F::zero() {
+            let value = self.clamp(value);
+            self.i.error =
+                (value - self.p.output() - self.d.output() - self.feedforward) / self.i.gain;
+        }
+    }
+
+    /// Applies the anti-windup strategy after the integral moved away from `integral`.
+    fn limit_windup(&mut self, error: F, integral: F, dt: F) {
+        let raw = self.raw_output();
+        let clamped = self.clamp(raw);
+        match self.anti_windup {
+            AntiWindup::None => {}
+            AntiWindup::ConditionalIntegration => {
+                let pushing = error * self.i.gain;
+                if (raw > clamped && pushing > F::zero()) || (raw < clamped && pushing < F::zero())
+                {
+                    self.i.error = integral;
+                }
+            }
+            AntiWindup::BackCalculation { tracking_gain } => {
+                if self.i.gain != F::zero() {
+                    self.i.error =
+                        self.i.error + tracking_gain * (clamped - raw) * dt / self.i.gain;
+                }
+            }
+        }
+    }
+
+    fn derivative_time_constant(&self) -> F {
+        match self.derivative_filter {
+            DerivativeFilter::None => F::zero(),
+            DerivativeFilter::TimeConstant(tf) => seconds(tf),
+            DerivativeFilter::N(n) if self.p.gain != F::zero() && n > F::zero() => {
+                (self.d.gain / self.p.gain).abs() / n
+            }
+            DerivativeFilter::N(_) => F::zero(),
+        }
+    }
+
+    /// Sum of the P, I and D terms plus feedforward, before output limits are applied.
+    fn raw_output(&self) -> F {
+        self.p.output() + self.i.output() + self.d.output() + self.feedforward
+    }
+
+    fn clamp(&self, value: F) -> F {
+        let (min, max) = self.output_limits;
+        value.max(min).min(max)
+    }
+
+    /// Captures the gains and accumulated state, e.g. to persist a long-running tuning
+    /// session across restarts.
+    pub fn snapshot(&self) -> PidSnapshot<F> {
+        PidSnapshot {
+            p: self.p.snapshot(),
+            i: self.i.snapshot(),
+            d: self.d.snapshot(),
+            goal: self.goal,
+            mode: self.mode,
+            manual_output: self.manual_output,
+            feedforward: self.feedforward,
+        }
+    }
+
+    /// Picks up from a `snapshot`. Time isn't part of the snapshot, so the next `update_at`
+    /// after a restore only seeds the clock.
+    pub fn restore(&mut self, snapshot: &PidSnapshot<F>) {
+        self.p.restore(&snapshot.p);
+        self.i.restore(&snapshot.i);
+        self.d.restore(&snapshot.d);
+        self.goal = snapshot.goal;
+        self.mode = snapshot.mode;
+        self.manual_output = snapshot.manual_output;
+        self.feedforward = snapshot.feedforward;
+        #[cfg(feature = "std")]
+        {
+            self.last_update = None;
+        }
+    }
+
+    /// How far the unclamped output is beyond the output limits: positive when pinned at
+    /// the maximum, negative when pinned at the minimum, and zero otherwise.
+    pub fn saturation(&self) -> F {
+        match self.mode {
+            ControlMode::Automatic => {
+                let raw = self.raw_output();
+                raw - self.clamp(raw)
+            }
+            ControlMode::Manual => F::zero(),
+        }
+    }
+
+    pub fn output(&self) -> F {
+        match self.mode {
+            ControlMode::Automatic => self.clamp(self.raw_output()),
+            ControlMode::Manual => self.clamp(self.manual_output),
+        }
+    }
+}
+
+/// Drives the controller towards `goal` requests per second, reading `output` as the
+/// worker count.
+impl<F: Real> ConcurrencyController for PidController<F> {
+    fn observe(&mut self, sample: Sample) {
+        if let Some(feedforward) = self.feedforward_fn {
+            self.feedforward = feedforward(self.goal, &sample);
+        }
+
+        self.update_with_dt(self.goal, real(sample.throughput()), sample.elapsed);
+    }
+
+    fn recommend(&self) -> usize {
+        // NaN is already gone after `max`, so only overflow can fail here
+        self.output().max(F::zero()).round().to_usize().unwrap_or(usize::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integral_accumulates_error_over_time() {
+        let mut pid = PidController::new((0.0, 1.0, 0.0));
+
+        pid.update_with_dt(10.0, 0.0, Duration::from_millis(500));
+        pid.update_with_dt(10.0, 0.0, Duration::from_millis(1500));
+
+        assert!((pid.output() - 20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn derivative_is_rate_of_change() {
+        let mut pid = PidController::new((0.0, 0.0, 1.0));
+
+        pid.update_with_dt(10.0, 0.0, Duration::from_millis(100));
+        assert_eq!(pid.output(), 0.0);
+
+        // error drops from 10 to 8 over 200ms, so -10 per second
+        pid.update_with_dt(10.0, 2.0, Duration::from_millis(200));
+        assert!((pid.output() + 10.0).abs() < 1e-4);
+
+        // a repeated timestamp carries no rate information, so the derivative holds
+        pid.update_with_dt(10.0, 2.0, Duration::from_secs(0));
+        assert!((pid.output() + 10.0).abs() < 1e-4);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn update_at_derives_dt_from_timestamps() {
+        let mut pid = PidController::new((0.0, 1.0, 0.0));
+        let start = Instant::now();
+
+        pid.update_at(4.0, 0.0, start);
+        assert_eq!(pid.output(), 0.0);
+
+        pid.update_at(4.0, 0.0, start + Duration::from_secs(2));
+        assert!((pid.output() - 8.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn derivative_on_measurement_ignores_goal_changes() {
+        let dt = Duration::from_millis(100);
+        let mut on_error = PidController::new((0.0, 0.0, 1.0));
+        let mut on_measurement = PidController::new((0.0, 0.0, 1.0))
+            .with_derivative_source(DerivativeSource::Measurement);
+
+        for pid in [&mut on_error, &mut on_measurement].iter_mut() {
+            pid.update_with_dt(10.0, 5.0, dt);
+            pid.update_with_dt(100.0, 5.0, dt);
+        }
+
+        assert!((on_error.output() - 900.0).abs() < 1e-2);
+        assert_eq!(on_measurement.output(), 0.0);
+    }
+
+    #[test]
+    fn derivative_filter_smooths_noise() {
+        let dt = Duration::from_millis(100);
+        let mut raw = PidController::new((0.0, 0.0, 1.0));
+        let mut filtered = PidController::new((0.0, 0.0, 1.0))
+            .with_derivative_filter(DerivativeFilter::TimeConstant(Duration::from_secs(1)));
+
+        let mut raw_peak = 0.0f32;
+        let mut filtered_peak = 0.0f32;
+        for tick in 0..20 {
+            let noise = if tick % 2 == 0 { 1.0 } else { -1.0 };
+            raw.update_with_dt(0.0, noise, dt);
+            filtered.update_with_dt(0.0, noise, dt);
+            raw_peak = raw_peak.max(raw.output().abs());
+            filtered_peak = filtered_peak.max(filtered.output().abs());
+        }
+
+        assert!(filtered_peak < raw_peak / 4.0);
+    }
+
+    #[test]
+    fn setpoint_weight_softens_proportional_kick() {
+        let mut pid = PidController::new((1.0, 0.0, 0.0)).with_setpoint_weight(0.5);
+
+        pid.update(100.0, 0.0);
+        assert_eq!(pid.output(), 50.0);
+
+        // the weight only applies to the goal, so disturbances get the full gain
+        pid.update(100.0, 10.0);
+        assert_eq!(pid.output(), 40.0);
+    }
+
+    #[test]
+    fn manual_to_automatic_is_bumpless() {
+        let dt = Duration::from_millis(100);
+        let mut pid = PidController::new((0.5, 1.0, 0.1));
+        for _ in 0..10 {
+            pid.update_with_dt(100.0, 20.0, dt);
+        }
+
+        pid.set_manual(50.0);
+        assert_eq!(pid.mode(), ControlMode::Manual);
+        for current in [30.0, 35.0, 40.0].iter() {
+            pid.update_with_dt(100.0, *current, dt);
+            assert_eq!(pid.output(), 50.0);
+        }
+
+        pid.set_automatic();
+        assert!((pid.output() - 50.0).abs() < 1e-3);
+
+        // the next automatic update moves on from there rather than snapping back
+        pid.update_with_dt(100.0, 40.0, dt);
+        assert!((pid.output() - 61.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn feedforward_adds_to_output() {
+        let mut pid = PidController::new((0.1, 0.0, 0.0));
+        pid.set_feedforward(20.0);
+
+        pid.update(100.0, 90.0);
+        assert_eq!(pid.output(), 21.0);
+    }
+
+    #[test]
+    fn littles_law_feedforward() {
+        let mut pid =
+            PidController::new((0.0, 0.0, 0.0)).with_goal(1000.0).with_feedforward(littles_law);
+
+        // 1000 rps at 50ms per request needs 50 workers
+        pid.observe(Sample {
+            elapsed: Duration::from_secs(1),
+            requests: 400,
+            latency: Duration::from_millis(50),
+            ..Sample::default()
+        });
+        assert_eq!(pid.recommend(), 50);
+    }
+
+    #[test]
+    fn deadband_ignores_small_errors() {
+        let mut pid = PidController::new((1.0, 1.0, 0.0)).with_deadband(5.0);
+
+        pid.update(100.0, 97.0);
+        pid.update(100.0, 104.0);
+        assert_eq!(pid.output(), 0.0);
+
+        // outside the band both terms see the error less the band
+        pid.update(100.0, 90.0);
+        assert_eq!(pid.output(), 10.0);
+
+        // so crossing the edge barely moves the output
+        let mut pid = PidController::new((1.0, 0.0, 0.0)).with_deadband(5.0);
+        pid.update(100.0, 95.0);
+        assert_eq!(pid.output(), 0.0);
+        pid.update(100.0, 94.99);
+        assert!(pid.output() < 0.02);
+    }
+
+    #[test]
+    fn set_gains_is_bumpless() {
+        let dt = Duration::from_millis(100);
+        let mut pid = PidController::new((1.0, 1.0, 0.5));
+        for current in [10.0, 20.0, 35.0].iter() {
+            pid.update_with_dt(100.0, *current, dt);
+        }
+
+        let before = pid.output();
+        pid.set_gains((4.0, 2.0, 2.0));
+        assert!((pid.output() - before).abs() < 1e-3);
+        assert_eq!(pid.gains(), (4.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn restore_resumes_from_snapshot() {
+        let dt = Duration::from_millis(100);
+        let mut original = PidController::new((0.5, 1.0, 0.1));
+        for current in [10.0, 20.0, 35.0].iter() {
+            original.update_with_dt(100.0, *current, dt);
+        }
+
+        let mut restored = PidController::new((0.0, 0.0, 0.0));
+        restored.restore(&original.snapshot());
+        assert_eq!(restored.gains(), original.gains());
+        assert_eq!(restored.output(), original.output());
+
+        original.update_with_dt(100.0, 50.0, dt);
+        restored.update_with_dt(100.0, 50.0, dt);
+        assert_eq!(restored.output(), original.output());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshot_round_trips_through_json() {
+        let mut pid = PidController::new((0.5, 1.0, 0.1));
+        pid.update(100.0, 10.0);
+        pid.set_manual(12.0);
+
+        let json = serde_json::to_string(&pid.snapshot()).unwrap();
+        let snapshot: PidSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot, pid.snapshot());
+    }
+
+    #[test]
+    fn generic_over_f64() {
+        let mut pid: PidController<f64> = PidController::new((0.0, 1.0, 0.0));
+
+        // a million ticks of a tiny error; in f32 the rounding error adds up past 1e-6
+        for _ in 0..1_000_000 {
+            pid.update_with_dt(1e-6, 0.0, Duration::from_secs(1));
+        }
+
+        assert!((pid.output() - 1.0).abs() < 1e-6);
+        assert_eq!(pid.recommend(), 1);
+    }
+
+    #[test]
+    fn output_is_clamped() {
+        let mut pid = PidController::new((1.0, 0.0, 0.0)).with_output_limits(1.0, 50.0);
+
+        pid.update(100.0, 0.0);
+        assert_eq!(pid.output(), 50.0);
+
+        pid.update(0.0, 100.0);
+        assert_eq!(pid.output(), 1.0);
+    }
+
+    /// Saturate the controller for a while, then drop the goal below the measurement and
+    /// count how many ticks it takes for the output to come off the ceiling.
+    fn ticks_to_unwind(anti_windup: AntiWindup) -> usize {
+        let mut pid = PidController::new((0.0, 1.0, 0.0))
+            .with_output_limits(0.0, 10.0)
+            .with_anti_windup(anti_windup);
+
+        let dt = Duration::from_millis(10);
+        for _ in 0..100 {
+            pid.update_with_dt(100.0, 0.0, dt);
+        }
+        assert_eq!(pid.output(), 10.0);
+
+        (0..1000)
+            .take_while(|_| {
+                pid.update_with_dt(0.0, 50.0, dt);
+                pid.output() >= 10.0
+            })
+            .count()
+    }
+
+    #[test]
+    fn anti_windup_recovers_from_saturation() {
+        let wound_up = ticks_to_unwind(AntiWindup::None);
+        let conditional = ticks_to_unwind(AntiWindup::ConditionalIntegration);
+        let back_calculation =
+            ticks_to_unwind(AntiWindup::BackCalculation { tracking_gain: 100.0 });
+
+        assert!(wound_up > 100);
+        assert!(conditional < 5);
+        assert!(back_calculation < 5);
     }
 }
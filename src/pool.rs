@@ -6,6 +6,7 @@ use async_std::{
     task,
 };
 use crossbeam_channel::{self, Receiver as CrossbeamReceiver, Sender as CrossbeamSender};
+use log::debug;
 use std::collections::VecDeque;
 
 /// # WorkerPool
@@ -76,10 +77,7 @@ impl<In, Out> Job<In, Out> {
     }
 
     pub fn stop_requested(&self) -> bool {
-        match self.close.try_recv() {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        self.close.try_recv().is_ok()
     }
 }
 
@@ -147,10 +145,7 @@ where
     /// Attempts to grab any immediately available results from the workers
     /// todo: Eh, I'm not sure this is a good API.
     pub fn try_next(&mut self) -> Option<Out> {
-        match self.results_channel.1.try_recv() {
-            Ok(out) => Some(out),
-            Err(_) => None,
-        }
+        self.results_channel.1.try_recv().ok()
     }
 
     pub fn command_channel(&self) -> crossbeam_channel::Sender<WorkerPoolCommand> {
@@ -201,8 +196,11 @@ where
                         n => n,
                     };
 
-                    println!("{}, {}", n, self.num_workers);
-                    self.num_workers = n;
+                    // nothing to do if the controller is holding steady
+                    if n != self.num_workers {
+                        debug!("WorkerPool, {}, {}", n, self.num_workers);
+                        self.num_workers = n;
+                    }
                 }
             }
         }
@@ -278,12 +276,12 @@ mod tests {
     use std::time::Duration;
 
     /// Double the input some number of times or until we receive a close message
-    async fn double(job: Job<(usize, usize), usize>) {
+    async fn double(job: Job<(usize, usize), usize>) -> JobStatus {
         let (mut i, n) = job.task;
         for _ in 0..n {
             // play nice with the pool by allowing it to stop this loop early
             if job.stop_requested() {
-                break;
+                return JobStatus::Stopped;
             }
 
             // do the actual work
@@ -295,6 +293,8 @@ mod tests {
             // pretend this is hard
             task::sleep(Duration::from_millis(100)).await;
         }
+
+        JobStatus::Done
     }
 
     #[async_test]
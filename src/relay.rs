@@ -0,0 +1,231 @@
+use crate::controller::{ConcurrencyController, Sample};
+use log::debug;
+use std::{f32::consts::PI, time::Duration};
+
+/// Rules for turning a relay experiment's ultimate gain and period into PID gains.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RelayTuningRule {
+    /// Classic Ziegler–Nichols. Fast, with roughly 25% overshoot.
+    ZieglerNichols,
+    /// Ziegler–Nichols with the derivative term dropped.
+    ZieglerNicholsPi,
+    /// Tyreus–Luyben. Much less aggressive than Ziegler–Nichols, with little overshoot.
+    TyreusLuyben,
+    /// Pessen integral rule. Aggressive; good disturbance rejection.
+    PessenIntegral,
+    /// Ziegler–Nichols variant that trades speed for some overshoot.
+    SomeOvershoot,
+    /// Ziegler–Nichols variant that trades more speed for no overshoot.
+    NoOvershoot,
+}
+
+/// What a relay experiment learned about the process.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RelayResult {
+    /// Proportional gain at which the closed loop would oscillate indefinitely
+    pub ultimate_gain: f32,
+    /// Period of that oscillation
+    pub ultimate_period: Duration,
+}
+
+impl RelayResult {
+    /// Recommended `(p, i, d)` gains for `PidController::new`, using `rule`.
+    pub fn gains(&self, rule: RelayTuningRule) -> (f32, f32, f32) {
+        let ku = self.ultimate_gain;
+        let tu = self.ultimate_period.as_secs_f32();
+
+        // (kp, ti, td), with ti and td in seconds. A ti of zero means no integral.
+        let (kp, ti, td) = match rule {
+            RelayTuningRule::ZieglerNichols => (0.6 * ku, tu / 2.0, tu / 8.0),
+            RelayTuningRule::ZieglerNicholsPi => (0.45 * ku, tu / 1.2, 0.0),
+            RelayTuningRule::TyreusLuyben => (ku / 2.2, 2.2 * tu, tu / 6.3),
+            RelayTuningRule::PessenIntegral => (0.7 * ku, 0.4 * tu, 0.15 * tu),
+            RelayTuningRule::SomeOvershoot => (ku / 3.0, tu / 2.0, tu / 3.0),
+            RelayTuningRule::NoOvershoot => (ku / 5.0, tu / 2.0, tu / 3.0),
+        };
+
+        let ki = if ti > 0.0 { kp / ti } else { 0.0 };
+        (kp, ki, kp * td)
+    }
+}
+
+/// # RelayTuner
+///
+/// Åström–Hägglund relay auto-tuning.
+///
+/// Instead of guessing gains, put the process in a controlled oscillation and measure it.
+/// The tuner acts as a relay: while the process value is below `setpoint` it recommends
+/// `high` workers, and while above it recommends `low`. Most processes respond to that
+/// with a steady oscillation whose amplitude and period reveal the ultimate gain and
+/// period, which the classic tuning rules turn into PID gains.
+///
+/// The tuner is itself a `ConcurrencyController`, so it can drive a `WorkerPool` through
+/// the same loop that will later run the tuned `PidController`. Feed it samples until
+/// `result` returns something.
+///
+/// ```
+/// use clobber::{PidController, RelayTuner, RelayTuningRule};
+///
+/// let tuner = RelayTuner::new(1000.0, 5, 15);
+/// // ... drive a pool with the tuner until tuner.result() is Some ...
+/// if let Some(result) = tuner.result() {
+///     let pid = PidController::new(result.gains(RelayTuningRule::TyreusLuyben));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RelayTuner {
+    setpoint: f32,
+    low: usize,
+    high: usize,
+    /// Band around the setpoint the process value must cross before the relay switches,
+    /// which keeps measurement noise from chattering the relay
+    hysteresis: f32,
+    /// Number of full oscillations to average over
+    cycles: usize,
+    output_high: bool,
+    /// Seconds since the experiment started, accumulated from sample intervals
+    elapsed: f32,
+    last_switch_high: Option<f32>,
+    cycle_max: f32,
+    cycle_min: f32,
+    periods: Vec<f32>,
+    amplitudes: Vec<f32>,
+}
+
+impl RelayTuner {
+    /// Creates a tuner that oscillates the process around `setpoint` by switching between
+    /// `low` and `high` workers.
+    pub fn new(setpoint: f32, low: usize, high: usize) -> Self {
+        assert!(low < high, "relay needs low < high");
+        Self {
+            setpoint,
+            low,
+            high,
+            hysteresis: 0.0,
+            cycles: 3,
+            output_high: true,
+            elapsed: 0.0,
+            last_switch_high: None,
+            cycle_max: f32::MIN,
+            cycle_min: f32::MAX,
+            periods: Vec::new(),
+            amplitudes: Vec::new(),
+        }
+    }
+
+    /// Sets the hysteresis band around the setpoint, in process value units.
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Self {
+        self.hysteresis = hysteresis.abs();
+        self
+    }
+
+    /// Sets how many oscillations are averaged before a result is reported.
+    pub fn with_cycles(mut self, cycles: usize) -> Self {
+        self.cycles = cycles.max(1);
+        self
+    }
+
+    /// Whether enough oscillations have been observed to produce a result.
+    pub fn is_done(&self) -> bool {
+        // the first cycle is start-up transient and doesn't count
+        self.periods.len() > self.cycles
+    }
+
+    /// The measured ultimate gain and period, once the experiment is done.
+    pub fn result(&self) -> Option<RelayResult> {
+        if !self.is_done() {
+            return None;
+        }
+
+        let measured = self.periods.len() - 1;
+        let period = self.periods[1..].iter().sum::<f32>() / measured as f32;
+        let amplitude = self.amplitudes[1..].iter().sum::<f32>() / measured as f32;
+        if amplitude <= self.hysteresis {
+            return None;
+        }
+
+        let relay_amplitude = (self.high - self.low) as f32 / 2.0;
+        let ultimate_gain = 4.0 * relay_amplitude
+            / (PI * (amplitude * amplitude - self.hysteresis * self.hysteresis).sqrt());
+
+        Some(RelayResult { ultimate_gain, ultimate_period: Duration::from_secs_f32(period) })
+    }
+}
+
+impl ConcurrencyController for RelayTuner {
+    fn observe(&mut self, sample: Sample) {
+        let value = sample.throughput();
+        self.elapsed += sample.elapsed.as_secs_f32();
+        self.cycle_max = self.cycle_max.max(value);
+        self.cycle_min = self.cycle_min.min(value);
+
+        if self.output_high && value > self.setpoint + self.hysteresis {
+            self.output_high = false;
+        } else if !self.output_high && value < self.setpoint - self.hysteresis {
+            self.output_high = true;
+
+            if let Some(last) = self.last_switch_high {
+                self.periods.push(self.elapsed - last);
+                self.amplitudes.push((self.cycle_max - self.cycle_min) / 2.0);
+                debug!("RelayTuner, {:?}, {:?}", self.periods.last(), self.amplitudes.last());
+            }
+            self.last_switch_high = Some(self.elapsed);
+            self.cycle_max = value;
+            self.cycle_min = value;
+        }
+    }
+
+    fn recommend(&self) -> usize {
+        if self.output_high {
+            self.high
+        } else {
+            self.low
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn tuning_rules() {
+        let result = RelayResult { ultimate_gain: 10.0, ultimate_period: Duration::from_secs(4) };
+
+        let (p, i, d) = result.gains(RelayTuningRule::ZieglerNichols);
+        assert!((p - 6.0).abs() < 1e-4);
+        assert!((i - 3.0).abs() < 1e-4);
+        assert!((d - 3.0).abs() < 1e-4);
+
+        let (_, _, d) = result.gains(RelayTuningRule::ZieglerNicholsPi);
+        assert_eq!(d, 0.0);
+    }
+
+    /// Each worker adds 100 rps after a lag of 1s and a dead time of 0.5s.
+    #[test]
+    fn finds_oscillation() {
+        let dt = Duration::from_millis(10);
+        let mut tuner = RelayTuner::new(1000.0, 5, 15).with_hysteresis(10.0);
+        let mut delay = VecDeque::from(vec![0usize; 50]);
+        let mut rps = 0.0f32;
+
+        for _ in 0..10_000 {
+            if tuner.is_done() {
+                break;
+            }
+
+            delay.push_back(tuner.recommend());
+            let workers = delay.pop_front().unwrap() as f32;
+            rps += (workers * 100.0 - rps) * dt.as_secs_f32();
+
+            let requests = (rps * dt.as_secs_f32()).round() as usize;
+            tuner.observe(Sample { elapsed: dt, requests, ..Sample::default() });
+        }
+
+        let result = tuner.result().expect("relay never settled into an oscillation");
+        let period = result.ultimate_period.as_secs_f32();
+        assert!(period > 1.0 && period < 3.0, "period {}", period);
+        assert!(result.ultimate_gain > 0.0);
+    }
+}
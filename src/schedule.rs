@@ -0,0 +1,177 @@
+use crate::{
+    controller::{ConcurrencyController, Sample},
+    pid::PidController,
+};
+use log::debug;
+
+/// The measurement used to look up gains in a `GainSchedule`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ScheduleVariable {
+    /// `Sample::workers`, or the last recommendation for samples that leave it at zero
+    Workers,
+    /// `Sample::throughput`
+    Throughput,
+}
+
+/// A table of `(p, i, d)` gains keyed by operating point.
+///
+/// Lookups between two entries interpolate linearly; lookups outside the table use the
+/// nearest entry.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GainSchedule {
+    /// Sorted by key
+    entries: Vec<(f32, (f32, f32, f32))>,
+}
+
+impl GainSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the gains to use at operating point `key`.
+    pub fn with_entry(mut self, key: f32, gain: (f32, f32, f32)) -> Self {
+        let index = self.entries.iter().position(|(k, _)| *k > key).unwrap_or(self.entries.len());
+        self.entries.insert(index, (key, gain));
+        self
+    }
+
+    /// Gains for operating point `key`. An empty schedule returns all zeroes.
+    pub fn gains_at(&self, key: f32) -> (f32, f32, f32) {
+        let upper = match self.entries.iter().position(|(k, _)| *k >= key) {
+            Some(upper) => upper,
+            None => return self.entries.last().map(|(_, gain)| *gain).unwrap_or_default(),
+        };
+        if upper == 0 {
+            return self.entries[0].1;
+        }
+
+        let (k0, g0) = self.entries[upper - 1];
+        let (k1, g1) = self.entries[upper];
+        let t = (key - k0) / (k1 - k0);
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+
+        (lerp(g0.0, g1.0), lerp(g0.1, g1.1), lerp(g0.2, g1.2))
+    }
+}
+
+/// # GainScheduledController
+///
+/// A `PidController` whose gains follow the operating point.
+///
+/// The gains that work at 10 workers are rarely the ones that work at 1000. Before every
+/// update this looks the current operating point up in a `GainSchedule` and hands the
+/// interpolated gains to the wrapped controller, which back-calculates its integral state
+/// so the output doesn't jump when the gains change. That needs an integral gain at every
+/// entry; without one, a change in P or D shows up in the output.
+///
+/// ```
+/// use clobber::{GainSchedule, GainScheduledController, PidController, ScheduleVariable};
+///
+/// let schedule = GainSchedule::new()
+///     .with_entry(10.0, (0.05, 0.01, 0.0))
+///     .with_entry(1000.0, (0.5, 0.1, 0.0));
+/// let pid = PidController::new((0.0, 0.0, 0.0)).with_goal(10_000.0);
+///
+/// let controller = GainScheduledController::new(pid, schedule, ScheduleVariable::Workers);
+/// ```
+pub struct GainScheduledController {
+    pid: PidController,
+    schedule: GainSchedule,
+    variable: ScheduleVariable,
+}
+
+impl GainScheduledController {
+    /// Wraps `pid`, which keeps its goal, limits and other settings; only its gains are
+    /// managed by `schedule`.
+    pub fn new(pid: PidController, schedule: GainSchedule, variable: ScheduleVariable) -> Self {
+        Self { pid, schedule, variable }
+    }
+
+    pub fn pid(&self) -> &PidController {
+        &self.pid
+    }
+
+    pub fn pid_mut(&mut self) -> &mut PidController {
+        &mut self.pid
+    }
+}
+
+impl ConcurrencyController for GainScheduledController {
+    fn observe(&mut self, sample: Sample) {
+        let key = match self.variable {
+            // most samples never fill in `workers`, so fall back to what we asked for
+            ScheduleVariable::Workers if sample.workers == 0 => self.pid.recommend() as f32,
+            ScheduleVariable::Workers => sample.workers as f32,
+            ScheduleVariable::Throughput => sample.throughput(),
+        };
+
+        let gains = self.schedule.gains_at(key);
+        debug!("GainScheduledController, {}, {:?}", key, gains);
+
+        self.pid.set_gains(gains);
+        self.pid.observe(sample);
+    }
+
+    fn recommend(&self) -> usize {
+        self.pid.recommend()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn interpolates_between_entries() {
+        let schedule =
+            GainSchedule::new().with_entry(100.0, (2.0, 0.2, 0.0)).with_entry(0.0, (1.0, 0.1, 0.0));
+
+        assert_eq!(schedule.gains_at(-5.0), (1.0, 0.1, 0.0));
+        assert_eq!(schedule.gains_at(50.0), (1.5, 0.15, 0.0));
+        assert_eq!(schedule.gains_at(500.0), (2.0, 0.2, 0.0));
+        assert_eq!(GainSchedule::new().gains_at(1.0), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn switching_gains_is_bumpless() {
+        let schedule =
+            GainSchedule::new().with_entry(1.0, (1.0, 1.0, 0.5)).with_entry(2.0, (4.0, 1.0, 2.0));
+        let pid = PidController::new((0.0, 0.0, 0.0)).with_goal(100.0);
+        let mut controller = GainScheduledController::new(pid, schedule, ScheduleVariable::Workers);
+
+        // build up some integral at the low operating point
+        let sample = Sample {
+            elapsed: Duration::from_millis(100),
+            requests: 5,
+            workers: 1,
+            ..Sample::default()
+        };
+        for _ in 0..10 {
+            controller.observe(sample);
+        }
+        let before = controller.pid().output();
+
+        // jump to the high operating point with the same error of 50; P quadruples, but the
+        // output only moves by one tick's worth of integral
+        controller.observe(Sample { workers: 2, ..sample });
+        assert!((controller.pid().output() - before - 5.0).abs() < 1e-3);
+        assert_eq!(controller.pid().gains(), (4.0, 1.0, 2.0));
+    }
+
+    #[test]
+    fn workers_default_to_the_last_recommendation() {
+        let schedule =
+            GainSchedule::new().with_entry(1.0, (0.0, 1.0, 0.0)).with_entry(10.0, (0.0, 2.0, 0.0));
+        let pid = PidController::new((0.0, 0.0, 0.0)).with_goal(100.0);
+        let mut controller = GainScheduledController::new(pid, schedule, ScheduleVariable::Workers);
+
+        // nothing reports `workers`, but the controller knows it asked for 100
+        let sample = Sample { elapsed: Duration::from_secs(1), ..Sample::default() };
+        controller.observe(sample);
+        assert_eq!(controller.recommend(), 100);
+
+        controller.observe(sample);
+        assert_eq!(controller.pid().gains(), (0.0, 2.0, 0.0));
+    }
+}
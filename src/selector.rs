@@ -0,0 +1,205 @@
+use crate::{
+    controller::{ConcurrencyController, ProcessVariable, Sample},
+    pid::PidController,
+};
+use log::debug;
+
+/// # SelectorController
+///
+/// Runs one `PidController` per objective and applies whichever asks for the fewest
+/// workers.
+///
+/// Real goals rarely look like "exactly 5000 rps". They look like "as much as possible,
+/// as long as p99 stays under 200ms and errors under 1%". Each of those is an objective
+/// with its own controller watching its own `ProcessVariable`, and the most conservative
+/// recommendation wins. `active` reports which objective that was on the last tick, which
+/// is the constraint currently holding the worker count back.
+///
+/// A PID controller pointed at latency or error rate naturally acts as a limit: while the
+/// measurement is under the goal the error is positive and its output keeps climbing, so
+/// it only takes over once the measurement approaches the goal. To maximise throughput
+/// rather than hold a particular rps, give the throughput objective a goal it can't reach
+/// and cap it with output limits. Keep that goal finite: with something like `f32::MAX` the
+/// integral overflows to infinity within a few ticks and the output turns into NaN.
+///
+/// Objectives that lose the selection are overridden, and left alone their integral
+/// terms would wind up chasing a worker count they never get. Each tick every overridden
+/// controller tracks the applied output instead, so whichever takes over next starts from
+/// where the pool actually is.
+///
+/// ```
+/// use clobber::{PidController, ProcessVariable, SelectorController};
+///
+/// let controller = SelectorController::new()
+///     // as much throughput as 500 workers can get, well above anything the target manages
+///     .with_objective(
+///         ProcessVariable::Throughput,
+///         PidController::new((0.0, 0.01, 0.0))
+///             .with_goal(1_000_000.0)
+///             .with_output_limits(1.0, 500.0),
+///     )
+///     // while keeping latency under 200ms
+///     .with_objective(
+///         ProcessVariable::Latency,
+///         PidController::new((0.0, 100.0, 0.0)).with_goal(0.2).with_output_limits(1.0, 500.0),
+///     )
+///     // and errors under 1%
+///     .with_objective(
+///         ProcessVariable::ErrorRate,
+///         PidController::new((0.0, 1000.0, 0.0)).with_goal(0.01).with_output_limits(1.0, 500.0),
+///     );
+/// ```
+#[derive(Default)]
+pub struct SelectorController {
+    objectives: Vec<(ProcessVariable, PidController)>,
+    /// The objective whose output was applied on the last tick
+    active: Option<ProcessVariable>,
+    output: f32,
+}
+
+impl SelectorController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an objective: `pid` holds `variable` at its goal.
+    pub fn with_objective(mut self, variable: ProcessVariable, pid: PidController) -> Self {
+        self.objectives.push((variable, pid));
+        self
+    }
+
+    /// The objective currently limiting the worker count, or `None` before the first
+    /// observation.
+    pub fn active(&self) -> Option<ProcessVariable> {
+        self.active
+    }
+
+    pub fn objectives(&self) -> &[(ProcessVariable, PidController)] {
+        &self.objectives
+    }
+
+    /// The controller for the first objective on `variable`, e.g. to move its goal.
+    pub fn objective_mut(&mut self, variable: ProcessVariable) -> Option<&mut PidController> {
+        self.objectives.iter_mut().find(|(v, _)| *v == variable).map(|(_, pid)| pid)
+    }
+}
+
+impl ConcurrencyController for SelectorController {
+    fn observe(&mut self, sample: Sample) {
+        for (variable, pid) in self.objectives.iter_mut() {
+            pid.update_with_dt(pid.goal(), variable.read(&sample), sample.elapsed);
+        }
+
+        let selected = self
+            .objectives
+            .iter()
+            .enumerate()
+            .min_by(|(_, (_, a)), (_, (_, b))| {
+                a.output().partial_cmp(&b.output()).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(i, (variable, pid))| (i, *variable, pid.output()));
+
+        if let Some((index, variable, output)) = selected {
+            for (i, (_, pid)) in self.objectives.iter_mut().enumerate() {
+                if i != index {
+                    pid.track(output);
+                }
+            }
+
+            self.active = Some(variable);
+            self.output = output;
+        }
+
+        debug!("SelectorController, {:?}, {}", self.active, self.output);
+    }
+
+    fn recommend(&self) -> usize {
+        self.output.max(0.0).round() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Distribution, QueueingService};
+    use std::time::Duration;
+
+    #[test]
+    fn most_conservative_objective_wins() {
+        let mut controller = SelectorController::new()
+            .with_objective(
+                ProcessVariable::Throughput,
+                PidController::new((0.1, 0.0, 0.0)).with_goal(1000.0),
+            )
+            .with_objective(
+                ProcessVariable::Latency,
+                PidController::new((100.0, 0.0, 0.0)).with_goal(0.2),
+            );
+        assert_eq!(controller.active(), None);
+
+        // throughput asks for 50, latency for 10
+        controller.observe(Sample::one_second(500, 100));
+        assert_eq!(controller.recommend(), 10);
+        assert_eq!(controller.active(), Some(ProcessVariable::Latency));
+
+        // throughput asks for 10, latency for 15
+        controller.observe(Sample::one_second(900, 50));
+        assert_eq!(controller.recommend(), 10);
+        assert_eq!(controller.active(), Some(ProcessVariable::Throughput));
+    }
+
+    #[test]
+    fn overridden_objectives_do_not_wind_up() {
+        let mut controller = SelectorController::new()
+            .with_objective(
+                ProcessVariable::Throughput,
+                PidController::new((0.0, 0.1, 0.0)).with_goal(1000.0),
+            )
+            .with_objective(
+                ProcessVariable::Latency,
+                PidController::new((0.0, 10.0, 0.0)).with_goal(0.2),
+            );
+
+        // latency is at its goal, so it holds the pool at zero while throughput starves
+        for _ in 0..100 {
+            controller.observe(Sample::one_second(0, 200));
+        }
+        assert_eq!(controller.active(), Some(ProcessVariable::Latency));
+        let throughput = &controller.objectives()[0].1;
+        assert!(throughput.output().abs() < 1e-3);
+
+        // lifting the latency limit hands control back to throughput, which picks up from
+        // where the pool is rather than from 100 seconds of starvation
+        controller.objective_mut(ProcessVariable::Latency).unwrap().set_goal(100.0);
+        controller.observe(Sample::one_second(0, 200));
+        assert_eq!(controller.active(), Some(ProcessVariable::Throughput));
+        assert_eq!(controller.recommend(), 100);
+    }
+
+    #[test]
+    fn maximises_throughput_under_latency_slo() {
+        // 8 servers at 10ms each, so 12 workers is 15ms of latency
+        let mut target = QueueingService::new(8, Distribution::Constant(Duration::from_millis(10)));
+        let mut controller = SelectorController::new()
+            .with_objective(
+                ProcessVariable::Throughput,
+                PidController::new((0.0, 0.01, 0.0))
+                    .with_goal(10_000.0)
+                    .with_output_limits(1.0, 100.0),
+            )
+            .with_objective(
+                ProcessVariable::Latency,
+                PidController::new((0.0, 200.0, 0.0))
+                    .with_goal(0.015)
+                    .with_output_limits(1.0, 100.0),
+            );
+
+        for _ in 0..600 {
+            let sample = target.run(controller.recommend(), Duration::from_millis(100));
+            controller.observe(sample);
+        }
+
+        assert_eq!(controller.active(), Some(ProcessVariable::Latency));
+        assert!((11..=13).contains(&controller.recommend()));
+    }
+}
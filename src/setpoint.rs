@@ -0,0 +1,225 @@
+use crate::{
+    controller::{ConcurrencyController, Sample},
+    pid::PidController,
+};
+use std::time::Duration;
+
+/// A goal that changes over time.
+pub trait Setpoint {
+    /// Moves time forward by `dt` and returns the goal at the new time.
+    fn advance(&mut self, dt: Duration) -> f32;
+}
+
+/// A fixed goal.
+impl Setpoint for f32 {
+    fn advance(&mut self, _dt: Duration) -> f32 {
+        *self
+    }
+}
+
+/// Moves towards a target at no more than a fixed rate.
+///
+/// Changing the target from 0 to 100000 rps in one step asks the controller to close the
+/// whole gap at once. Ramping spreads the change out so the pool can follow without
+/// saturating.
+///
+/// ```
+/// use clobber::{RampedSetpoint, Setpoint};
+/// use std::time::Duration;
+///
+/// let mut goal = RampedSetpoint::new(0.0, 1000.0);
+/// goal.set_target(5000.0);
+///
+/// assert_eq!(goal.advance(Duration::from_secs(2)), 2000.0);
+/// assert_eq!(goal.advance(Duration::from_secs(10)), 5000.0);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RampedSetpoint {
+    value: f32,
+    target: f32,
+    /// Maximum change per second
+    rate: f32,
+}
+
+impl RampedSetpoint {
+    /// Starts at `value`, moving at most `rate` units per second towards its target.
+    pub fn new(value: f32, rate: f32) -> Self {
+        assert!(rate > 0.0, "ramp rate must be positive");
+        Self { value, target: value, rate }
+    }
+
+    /// Sets the value to ramp towards.
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// The current, possibly still ramping, value.
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+}
+
+impl Setpoint for RampedSetpoint {
+    fn advance(&mut self, dt: Duration) -> f32 {
+        let step = self.rate * dt.as_secs_f32();
+        let gap = self.target - self.value;
+        self.value = if gap.abs() <= step { self.target } else { self.value + step * gap.signum() };
+        self.value
+    }
+}
+
+/// Follows a piecewise-linear profile of `(time, value)` points, holding the first value
+/// before the profile starts and the last value after it ends.
+///
+/// ```
+/// use clobber::{ProfileSetpoint, Setpoint};
+/// use std::time::Duration;
+///
+/// // warm up to 1000 rps over a minute, hold for a minute, then ramp up to 2000 over 30s
+/// let mut goal = ProfileSetpoint::new()
+///     .with_point(Duration::from_secs(0), 0.0)
+///     .with_point(Duration::from_secs(60), 1000.0)
+///     .with_point(Duration::from_secs(120), 1000.0)
+///     .with_point(Duration::from_secs(150), 2000.0);
+///
+/// assert_eq!(goal.advance(Duration::from_secs(30)), 500.0);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileSetpoint {
+    /// Sorted by time, in seconds
+    points: Vec<(f32, f32)>,
+    elapsed: f32,
+}
+
+impl ProfileSetpoint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a point the profile passes through.
+    pub fn with_point(mut self, at: Duration, value: f32) -> Self {
+        let at = at.as_secs_f32();
+        let index = self.points.iter().position(|(t, _)| *t > at).unwrap_or(self.points.len());
+        self.points.insert(index, (at, value));
+        self
+    }
+
+    /// The profile's value `at` seconds after it starts. An empty profile is always 0.
+    pub fn value_at(&self, at: Duration) -> f32 {
+        let at = at.as_secs_f32();
+        let upper = match self.points.iter().position(|(t, _)| *t >= at) {
+            Some(upper) => upper,
+            None => return self.points.last().map(|(_, v)| *v).unwrap_or_default(),
+        };
+        if upper == 0 {
+            return self.points[0].1;
+        }
+
+        let (t0, v0) = self.points[upper - 1];
+        let (t1, v1) = self.points[upper];
+        v0 + (v1 - v0) * (at - t0) / (t1 - t0)
+    }
+
+    /// Whether the profile has run past its last point.
+    pub fn is_done(&self) -> bool {
+        self.points.last().map(|(t, _)| self.elapsed >= *t).unwrap_or(true)
+    }
+}
+
+impl Setpoint for ProfileSetpoint {
+    fn advance(&mut self, dt: Duration) -> f32 {
+        self.elapsed += dt.as_secs_f32();
+        self.value_at(Duration::from_secs_f32(self.elapsed))
+    }
+}
+
+/// # SetpointFollower
+///
+/// Drives a `PidController` towards a moving goal. Every observation advances the
+/// `Setpoint` by the sample's interval and hands the result to the controller as its goal.
+///
+/// ```
+/// use clobber::{PidController, RampedSetpoint, SetpointFollower};
+///
+/// let pid = PidController::new((0.01, 0.01, 0.0)).with_setpoint_weight(0.5);
+/// let mut controller = SetpointFollower::new(pid, RampedSetpoint::new(0.0, 500.0));
+/// controller.setpoint_mut().set_target(100_000.0);
+/// ```
+pub struct SetpointFollower<S> {
+    pid: PidController,
+    setpoint: S,
+}
+
+impl<S: Setpoint> SetpointFollower<S> {
+    pub fn new(pid: PidController, setpoint: S) -> Self {
+        Self { pid, setpoint }
+    }
+
+    pub fn pid(&self) -> &PidController {
+        &self.pid
+    }
+
+    pub fn setpoint(&self) -> &S {
+        &self.setpoint
+    }
+
+    pub fn setpoint_mut(&mut self) -> &mut S {
+        &mut self.setpoint
+    }
+}
+
+impl<S: Setpoint> ConcurrencyController for SetpointFollower<S> {
+    fn observe(&mut self, sample: Sample) {
+        let goal = self.setpoint.advance(sample.elapsed);
+        self.pid.set_goal(goal);
+        self.pid.observe(sample);
+    }
+
+    fn recommend(&self) -> usize {
+        self.pid.recommend()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramp_moves_in_both_directions() {
+        let mut ramp = RampedSetpoint::new(100.0, 10.0);
+        ramp.set_target(0.0);
+
+        assert_eq!(ramp.advance(Duration::from_secs(3)), 70.0);
+        ramp.set_target(200.0);
+        assert_eq!(ramp.advance(Duration::from_secs(1)), 80.0);
+    }
+
+    #[test]
+    fn profile_interpolates_and_holds() {
+        let mut profile = ProfileSetpoint::new()
+            .with_point(Duration::from_secs(10), 100.0)
+            .with_point(Duration::from_secs(20), 300.0);
+
+        assert_eq!(profile.value_at(Duration::from_secs(0)), 100.0);
+        assert_eq!(profile.value_at(Duration::from_secs(15)), 200.0);
+        assert_eq!(profile.value_at(Duration::from_secs(60)), 300.0);
+
+        assert_eq!(profile.advance(Duration::from_secs(20)), 300.0);
+        assert!(profile.is_done());
+    }
+
+    #[test]
+    fn follower_feeds_goal_to_controller() {
+        let pid = PidController::new((1.0, 0.0, 0.0));
+        let mut controller = SetpointFollower::new(pid, RampedSetpoint::new(0.0, 10.0));
+        controller.setpoint_mut().set_target(100.0);
+
+        controller.observe(Sample { elapsed: Duration::from_secs(1), ..Sample::default() });
+        assert_eq!(controller.pid().goal(), 10.0);
+        assert_eq!(controller.recommend(), 10);
+    }
+}
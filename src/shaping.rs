@@ -0,0 +1,175 @@
+use crate::controller::{ConcurrencyController, Sample};
+
+/// # Hysteresis
+///
+/// Holds another controller's recommendation steady until it moves by more than `band`
+/// workers.
+///
+/// Measurements are noisy, and a controller that faithfully follows them will ask for a
+/// worker more, then a worker less, every tick. Each of those changes starts or stops a
+/// worker for no real benefit. Wrapping the controller in `Hysteresis` only passes on
+/// changes big enough to matter.
+///
+/// ```
+/// use clobber::{AimdController, ConcurrencyController, Hysteresis, Sample};
+///
+/// let mut controller = Hysteresis::new(AimdController::new(100), 2);
+/// let healthy = Sample { requests: 100, ..Sample::default() };
+///
+/// controller.observe(healthy);
+/// controller.observe(healthy);
+/// assert_eq!(controller.recommend(), 100);
+///
+/// controller.observe(healthy);
+/// assert_eq!(controller.recommend(), 103);
+/// ```
+pub struct Hysteresis<C> {
+    inner: C,
+    band: usize,
+    held: usize,
+}
+
+impl<C: ConcurrencyController> Hysteresis<C> {
+    pub fn new(inner: C, band: usize) -> Self {
+        let held = inner.recommend();
+        Self { inner, band, held }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+}
+
+impl<C: ConcurrencyController> ConcurrencyController for Hysteresis<C> {
+    fn observe(&mut self, sample: Sample) {
+        self.inner.observe(sample);
+
+        let recommended = self.inner.recommend();
+        if recommended.abs_diff(self.held) > self.band {
+            self.held = recommended;
+        }
+    }
+
+    fn recommend(&self) -> usize {
+        self.held
+    }
+}
+
+/// # SlewRateLimit
+///
+/// Caps how quickly another controller's recommendation can rise or fall.
+///
+/// A controller that suddenly asks for 500 more workers will get them all at once, and
+/// the target gets a thundering herd of new connections. `SlewRateLimit` lets the worker
+/// count move towards the recommendation at no more than `up` workers per second on the
+/// way up and `down` workers per second on the way down.
+///
+/// ```
+/// use clobber::{ConcurrencyController, PidController, Sample, SlewRateLimit};
+/// use std::time::Duration;
+///
+/// let pid = PidController::new((1.0, 0.0, 0.0)).with_goal(500.0);
+/// let mut controller = SlewRateLimit::new(pid, 10.0, 50.0);
+///
+/// controller.observe(Sample { elapsed: Duration::from_secs(2), ..Sample::default() });
+/// assert_eq!(controller.recommend(), 20);
+/// ```
+pub struct SlewRateLimit<C> {
+    inner: C,
+    /// Maximum workers added per second
+    up: f32,
+    /// Maximum workers removed per second
+    down: f32,
+    /// Kept fractional so that slow rates still make progress across short ticks
+    current: f32,
+}
+
+impl<C: ConcurrencyController> SlewRateLimit<C> {
+    /// Limits `inner` to adding `up` and removing `down` workers per second.
+    pub fn new(inner: C, up: f32, down: f32) -> Self {
+        assert!(up >= 0.0 && down >= 0.0, "slew rates must not be negative");
+        let current = inner.recommend() as f32;
+        Self { inner, up, down, current }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+}
+
+impl<C: ConcurrencyController> ConcurrencyController for SlewRateLimit<C> {
+    fn observe(&mut self, sample: Sample) {
+        self.inner.observe(sample);
+
+        let dt = sample.elapsed.as_secs_f32();
+        let target = self.inner.recommend() as f32;
+        self.current = target.max(self.current - self.down * dt).min(self.current + self.up * dt);
+    }
+
+    fn recommend(&self) -> usize {
+        self.current.round() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PidController;
+    use std::time::Duration;
+
+    #[test]
+    fn ignores_noise() {
+        let pid = PidController::new((1.0, 0.0, 0.0)).with_goal(100.0);
+        let mut controller = Hysteresis::new(pid, 1);
+
+        let mut changes = 0;
+        let mut last = controller.recommend();
+        for requests in [40, 41, 40, 39, 40, 41, 30, 31, 30].iter() {
+            controller.observe(Sample {
+                elapsed: Duration::from_secs(1),
+                requests: *requests,
+                ..Sample::default()
+            });
+
+            if controller.recommend() != last {
+                changes += 1;
+                last = controller.recommend();
+            }
+        }
+
+        // one move to 60, one to 70; everything else is noise
+        assert_eq!(changes, 2);
+        assert_eq!(controller.recommend(), 70);
+    }
+
+    #[test]
+    fn separate_rise_and_fall_rates() {
+        let pid = PidController::new((1.0, 0.0, 0.0)).with_goal(100.0);
+        let mut controller = SlewRateLimit::new(pid, 10.0, 40.0);
+        let tick = Sample { elapsed: Duration::from_millis(250), ..Sample::default() };
+
+        for _ in 0..4 {
+            controller.observe(tick);
+        }
+        assert_eq!(controller.recommend(), 10);
+
+        // the target is swamped, asking for zero workers; we're allowed to shed faster
+        controller.inner_mut().set_goal(0.0);
+        controller.observe(tick);
+        assert_eq!(controller.recommend(), 0);
+
+        // the inner controller isn't slowed down, only what gets passed on
+        controller.inner_mut().set_goal(100.0);
+        controller.observe(tick);
+        assert_eq!(controller.inner().recommend(), 100);
+        assert_eq!(controller.recommend(), 3);
+    }
+}
@@ -0,0 +1,764 @@
+//! # Simulation
+//!
+//! Discrete-time plant models for trying out controllers and gains without a live target.
+//!
+//! A `Plant` takes the controller's output (usually a worker count) and returns the
+//! process value it would have measured after one tick. Every plant starts at rest with a
+//! zero input, and each can add seeded Gaussian measurement noise, so a test that passes
+//! once passes every time.
+//!
+//! `QueueingService` goes a level deeper: a discrete-event simulation of a service with a
+//! fixed number of servers and a queue, which reports back to a controller exactly the
+//! way a real target would.
+//!
+//! ```
+//! use clobber::sim::{simulate, UslServer};
+//! use clobber::PidController;
+//! use std::time::Duration;
+//!
+//! let mut server = UslServer::new(100.0).with_contention(0.05).with_noise(20.0, 7);
+//! let mut pid = PidController::new((0.0, 0.01, 0.0)).with_output_limits(0.0, 50.0);
+//!
+//! let trace = simulate(&mut pid, &mut server, 1000.0, Duration::from_millis(100), 600);
+//! let analysis = trace.analyze().unwrap();
+//! assert!(analysis.steady_state_error.abs() < 50.0);
+//! ```
+
+use crate::{controller::Sample, FopdtModel, PidController, Trace, TracePoint};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    f32::consts::PI,
+    time::Duration,
+};
+
+/// A small, seedable pseudo-random number generator (xorshift64*).
+///
+/// Not remotely suitable for anything but simulation, which is the point: no extra
+/// dependency, and the same seed always produces the same run.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero, and nearby seeds start out correlated
+        let mut rng = Self { state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1 };
+        rng.next_u64();
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniformly distributed in `(0, 1]`.
+    pub fn uniform(&mut self) -> f32 {
+        ((self.next_u64() >> 40) as f32 + 1.0) / (1u64 << 24) as f32
+    }
+
+    /// Normally distributed with mean 0 and standard deviation 1.
+    pub fn normal(&mut self) -> f32 {
+        // Box–Muller
+        (-2.0 * self.uniform().ln()).sqrt() * (2.0 * PI * self.uniform()).cos()
+    }
+
+    /// Exponentially distributed with the given mean.
+    pub fn exponential(&mut self, mean: f32) -> f32 {
+        -mean * self.uniform().ln()
+    }
+}
+
+/// Additive Gaussian measurement noise.
+#[derive(Debug, Clone)]
+struct Noise {
+    std_dev: f32,
+    rng: Rng,
+}
+
+impl Noise {
+    fn new(std_dev: f32, seed: u64) -> Self {
+        assert!(std_dev >= 0.0, "noise standard deviation can't be negative");
+        Self { std_dev, rng: Rng::new(seed) }
+    }
+
+    fn none() -> Self {
+        Self::new(0.0, 0)
+    }
+
+    fn apply(&mut self, value: f32) -> f32 {
+        if self.std_dev > 0.0 {
+            value + self.std_dev * self.rng.normal()
+        } else {
+            value
+        }
+    }
+}
+
+/// A process the controller acts on.
+pub trait Plant {
+    /// Applies `input` for one tick of length `dt` and returns the measured process value
+    /// at the end of it.
+    fn step(&mut self, input: f32, dt: Duration) -> f32;
+}
+
+/// # FopdtPlant
+///
+/// A first-order-plus-dead-time process: the same model `StepResponse::fit` identifies,
+/// so a fitted model can be checked against the gains derived from it.
+///
+/// Inputs take `dead_time` to have any effect, then the process value moves towards
+/// `gain * input`, covering 63% of the distance every `time_constant`.
+#[derive(Debug, Clone)]
+pub struct FopdtPlant {
+    model: FopdtModel,
+    noise: Noise,
+    /// Inputs that haven't made it through the dead time yet, stamped with when they were
+    /// applied
+    pending: VecDeque<(f32, f32)>,
+    /// The input currently acting on the process
+    held: f32,
+    time: f32,
+    value: f32,
+}
+
+impl FopdtPlant {
+    pub fn new(model: FopdtModel) -> Self {
+        Self {
+            model,
+            noise: Noise::none(),
+            pending: VecDeque::new(),
+            held: 0.0,
+            time: 0.0,
+            value: 0.0,
+        }
+    }
+
+    /// Starts the plant settled at `input` instead of at rest.
+    pub fn with_initial_input(mut self, input: f32) -> Self {
+        self.held = input;
+        self.value = self.model.gain * input;
+        self
+    }
+
+    /// Adds Gaussian measurement noise with the given standard deviation.
+    pub fn with_noise(mut self, std_dev: f32, seed: u64) -> Self {
+        self.noise = Noise::new(std_dev, seed);
+        self
+    }
+
+    /// The true, noise-free process value.
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+}
+
+impl Plant for FopdtPlant {
+    fn step(&mut self, input: f32, dt: Duration) -> f32 {
+        let dt_secs = dt.as_secs_f32();
+        self.pending.push_back((self.time, input));
+
+        // whatever was applied a dead time before this tick starts acts on it, give or take
+        // half a tick so accumulated rounding can't shift the delay by a whole one
+        let cutoff = self.time - self.model.dead_time.as_secs_f32() + dt_secs / 2.0;
+        while self.pending.front().is_some_and(|(time, _)| *time <= cutoff) {
+            self.held = self.pending.pop_front().map_or(self.held, |(_, input)| input);
+        }
+        self.time += dt_secs;
+
+        let target = self.model.gain * self.held;
+        let tau = self.model.time_constant.as_secs_f32();
+        if tau > 0.0 {
+            self.value += (target - self.value) * (1.0 - (-dt_secs / tau).exp());
+        } else {
+            self.value = target;
+        }
+
+        self.noise.apply(self.value)
+    }
+}
+
+/// # IntegratingPlant
+///
+/// A process whose value changes at a rate proportional to the input, like a backlog that
+/// grows with every worker's output. There's no natural resting point, so anything but a
+/// zero input drifts forever; a controller on an integrating plant needs little or no
+/// integral action of its own.
+#[derive(Debug, Clone)]
+pub struct IntegratingPlant {
+    /// Change in process value per second, per unit of input
+    gain: f32,
+    noise: Noise,
+    value: f32,
+}
+
+impl IntegratingPlant {
+    pub fn new(gain: f32) -> Self {
+        Self { gain, noise: Noise::none(), value: 0.0 }
+    }
+
+    /// Adds Gaussian measurement noise with the given standard deviation.
+    pub fn with_noise(mut self, std_dev: f32, seed: u64) -> Self {
+        self.noise = Noise::new(std_dev, seed);
+        self
+    }
+
+    /// The true, noise-free process value.
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+}
+
+impl Plant for IntegratingPlant {
+    fn step(&mut self, input: f32, dt: Duration) -> f32 {
+        self.value += self.gain * input * dt.as_secs_f32();
+        self.noise.apply(self.value)
+    }
+}
+
+/// # UslServer
+///
+/// A server whose throughput follows Gunther's Universal Scalability Law:
+///
+/// ```txt
+/// X(N) = λN / (1 + σ(N - 1) + κN(N - 1))
+/// ```
+///
+/// `λ` is the throughput of a single worker, `σ` the contention for shared resources
+/// and `κ` the cost of keeping workers coherent. With any coherency cost, throughput
+/// peaks at `sqrt((1 - σ) / κ)` workers and falls beyond that, which is exactly the
+/// shape a worker pool sees when it pushes a real service too hard.
+///
+/// The server responds instantly; wrap it in a controller with some lag if you need one.
+#[derive(Debug, Clone)]
+pub struct UslServer {
+    lambda: f32,
+    sigma: f32,
+    kappa: f32,
+    noise: Noise,
+}
+
+impl UslServer {
+    /// Creates a server that scales perfectly at `lambda` rps per worker.
+    pub fn new(lambda: f32) -> Self {
+        assert!(lambda > 0.0, "per-worker throughput must be positive");
+        Self { lambda, sigma: 0.0, kappa: 0.0, noise: Noise::none() }
+    }
+
+    /// Sets the contention coefficient `σ`, between 0 and 1.
+    pub fn with_contention(mut self, sigma: f32) -> Self {
+        assert!((0.0..=1.0).contains(&sigma), "contention must be between 0 and 1");
+        self.sigma = sigma;
+        self
+    }
+
+    /// Sets the coherency coefficient `κ`.
+    pub fn with_coherency(mut self, kappa: f32) -> Self {
+        assert!(kappa >= 0.0, "coherency can't be negative");
+        self.kappa = kappa;
+        self
+    }
+
+    /// Adds Gaussian noise with the given standard deviation, in rps, to measured
+    /// throughput.
+    pub fn with_noise(mut self, std_dev: f32, seed: u64) -> Self {
+        self.noise = Noise::new(std_dev, seed);
+        self
+    }
+
+    /// Noise-free throughput in rps with `workers` busy.
+    pub fn throughput(&self, workers: f32) -> f32 {
+        let n = workers.max(0.0);
+        let penalty = 1.0 + self.sigma * (n - 1.0).max(0.0) + self.kappa * n * (n - 1.0).max(0.0);
+        self.lambda * n / penalty
+    }
+
+    /// Request latency with `workers` busy, by Little's law.
+    pub fn latency(&self, workers: f32) -> Duration {
+        let n = workers.max(1.0);
+        Duration::from_secs_f32(n / self.throughput(n))
+    }
+
+    /// The worker count with the highest throughput, or `None` if throughput never stops
+    /// growing.
+    pub fn peak(&self) -> Option<f32> {
+        if self.kappa > 0.0 {
+            Some(((1.0 - self.sigma) / self.kappa).sqrt())
+        } else {
+            None
+        }
+    }
+
+    /// Runs `workers` for `elapsed` and reports it the way a worker pool would, for
+    /// driving any `ConcurrencyController`.
+    pub fn sample(&mut self, workers: usize, elapsed: Duration) -> Sample {
+        let throughput = self.noise.apply(self.throughput(workers as f32)).max(0.0);
+        Sample {
+            elapsed,
+            requests: (throughput * elapsed.as_secs_f32()).round() as usize,
+            errors: 0,
+            latency: self.latency(workers as f32),
+            workers,
+            rate: None,
+        }
+    }
+}
+
+impl Plant for UslServer {
+    fn step(&mut self, input: f32, _dt: Duration) -> f32 {
+        self.noise.apply(self.throughput(input)).max(0.0)
+    }
+}
+
+/// A distribution of durations, for inter-arrival and service times.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Distribution {
+    /// Always the same duration
+    Constant(Duration),
+    /// Exponentially distributed with the given mean, the "M" in M/M/c
+    Exponential(Duration),
+    /// Uniformly distributed between the two bounds, in either order
+    Uniform(Duration, Duration),
+}
+
+impl Distribution {
+    pub fn sample(&self, rng: &mut Rng) -> Duration {
+        match *self {
+            Distribution::Constant(duration) => duration,
+            Distribution::Exponential(mean) => mean.mul_f32(rng.exponential(1.0)),
+            Distribution::Uniform(a, b) => {
+                let (low, high) = (a.min(b), a.max(b));
+                low + (high - low).mul_f32(1.0 - rng.uniform())
+            }
+        }
+    }
+
+    pub fn mean(&self) -> Duration {
+        match *self {
+            Distribution::Constant(duration) | Distribution::Exponential(duration) => duration,
+            Distribution::Uniform(low, high) => (low + high) / 2,
+        }
+    }
+}
+
+/// How long a rejected request takes to come back as an error.
+const REJECTION_LATENCY: Duration = Duration::from_millis(1);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    /// Background traffic arrives
+    Arrival,
+    /// A server finishes a request that arrived at the given time (in nanos)
+    Completion { worker: bool, arrived: u64 },
+    /// A worker gets its rejection back
+    Rejected,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Request {
+    worker: bool,
+    arrived: u64,
+}
+
+/// # QueueingService
+///
+/// A discrete-event simulation of a service with `c` identical servers in front of a
+/// FIFO queue, running in virtual time.
+///
+/// The worker pool's workers are modelled as closed-loop clients: each sends a request,
+/// waits for the response, and immediately sends the next, exactly like a `WorkerPool`
+/// job. Optional background traffic arrives open-loop, so with exponential arrival and
+/// service times the service is an M/M/c queue that the pool's workers compete for.
+///
+/// When the queue is bounded, requests that find it full are rejected and come back to
+/// the worker as errors after a millisecond.
+///
+/// `run` advances the clock and reports what the pool's workers saw as a `Sample`, so
+/// any `ConcurrencyController` can be driven against it:
+///
+/// ```
+/// use clobber::sim::{Distribution, QueueingService};
+/// use clobber::{AimdController, ConcurrencyController};
+/// use std::time::Duration;
+///
+/// let service = Distribution::Exponential(Duration::from_millis(10));
+/// let mut target = QueueingService::new(8, service).with_queue_limit(16).with_seed(1);
+/// let mut controller = AimdController::new(1).with_limits(1, 100);
+///
+/// for _ in 0..300 {
+///     let sample = target.run(controller.recommend(), Duration::from_millis(100));
+///     controller.observe(sample);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct QueueingService {
+    servers: usize,
+    service: Distribution,
+    arrivals: Option<Distribution>,
+    queue_limit: Option<usize>,
+    rng: Rng,
+    /// Virtual time, in nanos
+    now: u64,
+    /// Pending events keyed by time and then insertion order, so ties run first come
+    /// first served
+    events: BinaryHeap<Reverse<(u64, u64, Event)>>,
+    next_id: u64,
+    busy: usize,
+    queue: VecDeque<Request>,
+    /// Workers currently sending requests
+    workers: usize,
+    /// Workers that will stop once their in-flight request comes back
+    retiring: usize,
+    /// Worker requests finished this interval, including errors
+    requests: usize,
+    errors: usize,
+    /// Total latency of successful worker requests this interval, in nanos
+    latency: u64,
+}
+
+impl QueueingService {
+    pub fn new(servers: usize, service: Distribution) -> Self {
+        assert!(servers > 0, "a service needs at least one server");
+        assert!(service.mean() > Duration::from_secs(0), "service time must be positive");
+        Self {
+            servers,
+            service,
+            arrivals: None,
+            queue_limit: None,
+            rng: Rng::new(0),
+            now: 0,
+            events: BinaryHeap::new(),
+            next_id: 0,
+            busy: 0,
+            queue: VecDeque::new(),
+            workers: 0,
+            retiring: 0,
+            requests: 0,
+            errors: 0,
+            latency: 0,
+        }
+    }
+
+    /// Adds open-loop background traffic with the given inter-arrival times.
+    pub fn with_arrivals(mut self, arrivals: Distribution) -> Self {
+        assert!(arrivals.mean() > Duration::from_secs(0), "inter-arrival time must be positive");
+        self.arrivals = Some(arrivals);
+        let first = self.now + nanos(arrivals.sample(&mut self.rng));
+        self.schedule(first, Event::Arrival);
+        self
+    }
+
+    /// Limits how many requests can wait for a server. Unbounded by default.
+    pub fn with_queue_limit(mut self, limit: usize) -> Self {
+        self.queue_limit = Some(limit);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    /// Changes the service time distribution from now on, e.g. to add latency to the
+    /// target halfway through a run. Requests already being served are unaffected.
+    pub fn set_service(&mut self, service: Distribution) {
+        assert!(service.mean() > Duration::from_secs(0), "service time must be positive");
+        self.service = service;
+    }
+
+    /// Virtual time elapsed since the simulation started.
+    pub fn now(&self) -> Duration {
+        Duration::from_nanos(self.now)
+    }
+
+    /// Requests waiting for a server.
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Runs the service for `elapsed` of virtual time with `workers` workers sending
+    /// requests, and reports what those workers saw.
+    pub fn run(&mut self, workers: usize, elapsed: Duration) -> Sample {
+        self.set_workers(workers);
+
+        let end = self.now + nanos(elapsed);
+        while let Some(Reverse((time, _, event))) = self.events.peek().copied() {
+            if time > end {
+                break;
+            }
+            self.events.pop();
+            self.now = time;
+            self.handle(event);
+        }
+        self.now = end;
+
+        let successes = self.requests - self.errors;
+        let latency = if successes > 0 { self.latency / successes as u64 } else { 0 };
+        let sample = Sample {
+            elapsed,
+            requests: self.requests,
+            errors: self.errors,
+            latency: Duration::from_nanos(latency),
+            workers,
+            rate: None,
+        };
+
+        self.requests = 0;
+        self.errors = 0;
+        self.latency = 0;
+        sample
+    }
+
+    fn set_workers(&mut self, workers: usize) {
+        let active = self.workers - self.retiring;
+        if workers > active {
+            // take back retirements before starting anyone new
+            let rehired = self.retiring.min(workers - active);
+            self.retiring -= rehired;
+            for _ in 0..workers - active - rehired {
+                self.workers += 1;
+                self.submit(true);
+            }
+        } else {
+            self.retiring += active - workers;
+        }
+    }
+
+    fn schedule(&mut self, time: u64, event: Event) {
+        self.events.push(Reverse((time, self.next_id, event)));
+        self.next_id += 1;
+    }
+
+    fn submit(&mut self, worker: bool) {
+        let request = Request { worker, arrived: self.now };
+        if self.busy < self.servers {
+            self.serve(request);
+        } else if self.queue_limit.is_none_or(|limit| self.queue.len() < limit) {
+            self.queue.push_back(request);
+        } else if worker {
+            self.schedule(self.now + nanos(REJECTION_LATENCY), Event::Rejected);
+        }
+    }
+
+    fn serve(&mut self, request: Request) {
+        self.busy += 1;
+        let done = self.now + nanos(self.service.sample(&mut self.rng));
+        self.schedule(done, Event::Completion { worker: request.worker, arrived: request.arrived });
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Arrival => {
+                self.submit(false);
+                if let Some(arrivals) = self.arrivals {
+                    let next = self.now + nanos(arrivals.sample(&mut self.rng));
+                    self.schedule(next, Event::Arrival);
+                }
+            }
+            Event::Completion { worker, arrived } => {
+                self.busy -= 1;
+                if let Some(next) = self.queue.pop_front() {
+                    self.serve(next);
+                }
+                if worker {
+                    self.requests += 1;
+                    self.latency += self.now - arrived;
+                    self.worker_done();
+                }
+            }
+            Event::Rejected => {
+                self.requests += 1;
+                self.errors += 1;
+                self.worker_done();
+            }
+        }
+    }
+
+    /// A worker got its response back: either retire it or send its next request.
+    fn worker_done(&mut self) {
+        if self.retiring > 0 {
+            self.retiring -= 1;
+            self.workers -= 1;
+        } else {
+            self.submit(true);
+        }
+    }
+}
+
+impl Plant for QueueingService {
+    /// Runs `input` workers for one tick and measures their throughput.
+    fn step(&mut self, input: f32, dt: Duration) -> f32 {
+        self.run(input.max(0.0).round() as usize, dt).throughput()
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos() as u64
+}
+
+/// Closes the loop between `pid` and `plant` for `ticks` ticks of length `dt`, holding
+/// the setpoint at `setpoint`, and records what happened for `Trace::analyze`.
+///
+/// The plant starts at rest. Each tick the controller sees the latest measurement, and
+/// its output is applied to the plant for the following tick.
+pub fn simulate<P: Plant>(
+    pid: &mut PidController,
+    plant: &mut P,
+    setpoint: f32,
+    dt: Duration,
+    ticks: usize,
+) -> Trace {
+    let mut trace = Trace::new();
+    let mut value = 0.0;
+
+    for tick in 0..ticks {
+        pid.update_with_dt(setpoint, value, dt);
+        let output = pid.output();
+        trace.push(TracePoint { time: tick as f32 * dt.as_secs_f32(), setpoint, value, output });
+        value = plant.step(output, dt);
+    }
+
+    trace
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn fopdt_step_response() {
+        let model = FopdtModel {
+            gain: 2.0,
+            time_constant: Duration::from_secs(1),
+            dead_time: Duration::from_millis(500),
+        };
+        let mut plant = FopdtPlant::new(model);
+
+        // nothing happens during the dead time
+        for _ in 0..5 {
+            assert_eq!(plant.step(10.0, TICK), 0.0);
+        }
+
+        // then 63% of the way there one time constant later
+        for _ in 0..10 {
+            plant.step(10.0, TICK);
+        }
+        assert!((plant.value() - 20.0 * 0.632).abs() < 0.1);
+    }
+
+    #[test]
+    fn usl_peaks_and_retrogrades() {
+        let server = UslServer::new(100.0).with_contention(0.1).with_coherency(0.001);
+        let peak = server.peak().unwrap();
+
+        assert!((peak - 30.0).abs() < 1e-3);
+        assert!(server.throughput(peak) > server.throughput(peak - 5.0));
+        assert!(server.throughput(peak) > server.throughput(peak + 5.0));
+        assert_eq!(server.throughput(1.0), 100.0);
+        assert_eq!(server.latency(1.0), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn noise_is_repeatable() {
+        let run = |seed| {
+            let mut plant = IntegratingPlant::new(1.0).with_noise(5.0, seed);
+            (0..10).map(|_| plant.step(1.0, TICK)).collect::<Vec<f32>>()
+        };
+
+        assert_eq!(run(3), run(3));
+        assert_ne!(run(3), run(4));
+    }
+
+    #[test]
+    fn uniform_bounds_in_either_order() {
+        let (low, high) = (Duration::from_millis(5), Duration::from_millis(10));
+        let mut rng = Rng::new(2);
+
+        for distribution in
+            [Distribution::Uniform(low, high), Distribution::Uniform(high, low)].iter()
+        {
+            assert_eq!(distribution.mean(), Duration::from_micros(7500));
+            for _ in 0..100 {
+                let sample = distribution.sample(&mut rng);
+                assert!(sample >= low && sample <= high);
+            }
+        }
+    }
+
+    #[test]
+    fn closed_loop_workers_obey_littles_law() {
+        let service = Distribution::Constant(Duration::from_millis(10));
+        let mut target = QueueingService::new(4, service);
+
+        // under capacity every worker gets a server to itself
+        let sample = target.run(2, Duration::from_secs(1));
+        assert_eq!(sample.requests, 200);
+        assert_eq!(sample.latency, Duration::from_millis(10));
+
+        // over capacity throughput flattens out and the extra workers wait in line
+        target.run(8, Duration::from_secs(1));
+        let sample = target.run(8, Duration::from_secs(1));
+        assert_eq!(sample.requests, 400);
+        assert_eq!(sample.latency, Duration::from_millis(20));
+
+        // shedding workers takes effect as their requests come back
+        target.run(1, Duration::from_secs(1));
+        assert_eq!(target.run(1, Duration::from_secs(1)).requests, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "service time must be positive")]
+    fn zero_service_time_is_rejected() {
+        // every completion would resubmit at the same instant and the clock would never move
+        QueueingService::new(2, Distribution::Constant(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn full_queue_rejects_workers() {
+        let service = Distribution::Constant(Duration::from_millis(10));
+        let mut target = QueueingService::new(1, service).with_queue_limit(1);
+
+        let sample = target.run(4, Duration::from_secs(1));
+        assert_eq!(sample.requests - sample.errors, 100);
+        assert!(sample.error_rate() > 0.5);
+    }
+
+    #[test]
+    fn background_traffic_adds_latency() {
+        let service = Distribution::Exponential(Duration::from_millis(10));
+        let quiet = QueueingService::new(2, service).with_seed(5);
+        let busy = quiet.clone().with_arrivals(Distribution::Exponential(Duration::from_millis(7)));
+
+        let latency = |mut target: QueueingService| {
+            target.run(1, Duration::from_secs(1));
+            target.run(1, Duration::from_secs(60)).latency
+        };
+
+        let quiet = latency(quiet);
+        let busy = latency(busy);
+        assert!((quiet.as_secs_f32() - 0.010).abs() < 0.001);
+        assert!(busy > quiet * 2);
+    }
+
+    #[test]
+    fn pi_settles_on_fopdt() {
+        let model = FopdtModel {
+            gain: 10.0,
+            time_constant: Duration::from_secs(2),
+            dead_time: Duration::from_millis(300),
+        };
+        let gains = model.gains(crate::StepTuningRule::Simc);
+        let mut pid = PidController::new(gains);
+        let mut plant = FopdtPlant::new(model).with_noise(1.0, 1);
+
+        let analysis = simulate(&mut pid, &mut plant, 100.0, TICK, 300).analyze().unwrap();
+        assert!(analysis.steady_state_error.abs() < 2.0);
+        assert!(analysis.overshoot < 15.0);
+    }
+}
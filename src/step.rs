@@ -0,0 +1,295 @@
+use crate::controller::{ConcurrencyController, Sample};
+use log::debug;
+use std::time::Duration;
+
+/// Rules for turning a first-order-plus-dead-time model into PID gains.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StepTuningRule {
+    /// Ziegler–Nichols open-loop (reaction curve). Aggressive, tends to overshoot.
+    ZieglerNichols,
+    /// Cohen–Coon. Handles larger dead time than Ziegler–Nichols, still aggressive.
+    CohenCoon,
+    /// Skogestad's SIMC PI rule with the closed-loop time constant set to the dead time,
+    /// the recommended choice for tight but robust control.
+    Simc,
+    /// SIMC with an explicit closed-loop time constant. Larger values are slower and more
+    /// robust.
+    SimcWithTarget(Duration),
+}
+
+/// A first-order-plus-dead-time process model: after `dead_time` passes, the process value
+/// moves towards `gain` times the change in workers, covering 63% of the distance every
+/// `time_constant`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FopdtModel {
+    /// Change in process value per additional worker at steady state
+    pub gain: f32,
+    pub time_constant: Duration,
+    pub dead_time: Duration,
+}
+
+impl FopdtModel {
+    /// Recommended `(p, i, d)` gains for `PidController::new`, using `rule`.
+    pub fn gains(&self, rule: StepTuningRule) -> (f32, f32, f32) {
+        let k = self.gain;
+        let tau = self.time_constant.as_secs_f32();
+        // a zero dead time makes every rule here infinitely aggressive
+        let theta = self.dead_time.as_secs_f32().max(1e-3);
+
+        // (kp, ti, td), with ti and td in seconds
+        let (kp, ti, td) = match rule {
+            StepTuningRule::ZieglerNichols => (1.2 * tau / (k * theta), 2.0 * theta, 0.5 * theta),
+            StepTuningRule::CohenCoon => {
+                let r = theta / tau;
+                (
+                    (tau / (k * theta)) * (4.0 / 3.0 + r / 4.0),
+                    theta * (32.0 + 6.0 * r) / (13.0 + 8.0 * r),
+                    4.0 * theta / (11.0 + 2.0 * r),
+                )
+            }
+            StepTuningRule::Simc => simc(k, tau, theta, theta),
+            StepTuningRule::SimcWithTarget(tau_c) => simc(k, tau, theta, tau_c.as_secs_f32()),
+        };
+
+        (kp, kp / ti, kp * td)
+    }
+}
+
+fn simc(k: f32, tau: f32, theta: f32, tau_c: f32) -> (f32, f32, f32) {
+    let kp = tau / (k * (tau_c + theta));
+    let ti = tau.min(4.0 * (tau_c + theta));
+    (kp, ti, 0.0)
+}
+
+/// One observation from a step test.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StepPoint {
+    /// Seconds since the test started
+    pub time: f32,
+    /// Worker count in effect
+    pub output: f32,
+    /// Process value, e.g. rps
+    pub value: f32,
+}
+
+/// A recorded open-loop step response.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StepResponse {
+    points: Vec<StepPoint>,
+}
+
+impl StepResponse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, point: StepPoint) {
+        self.points.push(point);
+    }
+
+    pub fn points(&self) -> &[StepPoint] {
+        &self.points
+    }
+
+    /// Fits a first-order-plus-dead-time model using the two-point method: the times at
+    /// which the response covers 28.3% and 63.2% of its final change pin down both the
+    /// time constant and the dead time.
+    ///
+    /// The process value is assumed steady before the step, and settled over the last
+    /// tenth of the recording. Returns `None` if there is no step, no response, or the
+    /// response never crosses both points.
+    pub fn fit(&self) -> Option<FopdtModel> {
+        let first = self.points.first()?;
+        let step = self.points.iter().position(|p| p.output != first.output)?;
+        let step_time = self.points[step].time;
+
+        let before = &self.points[..step];
+        let start = before.iter().map(|p| p.value).sum::<f32>() / before.len() as f32;
+
+        let after = &self.points[step..];
+        let tail = &after[after.len() - (after.len() / 10).max(1)..];
+        let end = tail.iter().map(|p| p.value).sum::<f32>() / tail.len() as f32;
+
+        let delta_output = after[after.len() - 1].output - first.output;
+        let delta_value = end - start;
+        if delta_value == 0.0 {
+            return None;
+        }
+
+        // first time the response covers `fraction` of its total change, interpolating
+        // between the two samples that straddle it
+        let progress = |p: &StepPoint| (p.value - start) / delta_value;
+        let crossing = |fraction: f32| {
+            let j = after.iter().position(|p| progress(p) >= fraction)?;
+            if j == 0 {
+                return Some(after[0].time);
+            }
+
+            let (a, b) = (&after[j - 1], &after[j]);
+            let (ya, yb) = (progress(a), progress(b));
+            Some(a.time + (b.time - a.time) * (fraction - ya) / (yb - ya))
+        };
+
+        let t28 = crossing(0.283)? - step_time;
+        let t63 = crossing(0.632)? - step_time;
+        let tau = 1.5 * (t63 - t28);
+        if tau <= 0.0 {
+            return None;
+        }
+        let theta = (t63 - tau).max(0.0);
+
+        Some(FopdtModel {
+            gain: delta_value / delta_output,
+            time_constant: Duration::from_secs_f32(tau),
+            dead_time: Duration::from_secs_f32(theta),
+        })
+    }
+}
+
+/// # StepTest
+///
+/// An open-loop step test that can drive a `WorkerPool` directly.
+///
+/// It holds the pool at `initial` workers long enough to measure a baseline, bumps it to
+/// `stepped` workers, and records how the process value responds. Once `is_done`, `fit`
+/// gives a `FopdtModel` whose `gains` are ready for `PidController::new`.
+///
+/// Every observation is also logged at `debug` level, so a test run captured with
+/// `tuning::setup_logger` can be fitted later with `tuning::read_step_response`.
+#[derive(Debug, Clone)]
+pub struct StepTest {
+    initial: usize,
+    stepped: usize,
+    baseline: f32,
+    duration: f32,
+    /// Seconds since the test started, accumulated from sample intervals
+    elapsed: f32,
+    response: StepResponse,
+}
+
+impl StepTest {
+    /// Creates a test that steps from `initial` to `stepped` workers after a 10 second
+    /// baseline, then records for 60 seconds.
+    pub fn new(initial: usize, stepped: usize) -> Self {
+        assert_ne!(initial, stepped, "a step test needs a step");
+        Self {
+            initial,
+            stepped,
+            baseline: 10.0,
+            duration: 60.0,
+            elapsed: 0.0,
+            response: StepResponse::new(),
+        }
+    }
+
+    /// Sets how long to hold `initial` workers before stepping.
+    pub fn with_baseline(mut self, baseline: Duration) -> Self {
+        self.baseline = baseline.as_secs_f32();
+        self
+    }
+
+    /// Sets how long to record after the step. This should comfortably exceed the time the
+    /// process takes to settle.
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration.as_secs_f32();
+        self
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.baseline + self.duration
+    }
+
+    pub fn response(&self) -> &StepResponse {
+        &self.response
+    }
+
+    /// Fits a model to the response recorded so far.
+    pub fn fit(&self) -> Option<FopdtModel> {
+        self.response.fit()
+    }
+}
+
+impl ConcurrencyController for StepTest {
+    fn observe(&mut self, sample: Sample) {
+        // the sample describes the interval we just finished, under the previous output
+        let output = self.recommend() as f32;
+        self.elapsed += sample.elapsed.as_secs_f32();
+
+        let point = StepPoint { time: self.elapsed, output, value: sample.throughput() };
+        debug!("StepTest, {}, {}, {}", point.time, point.output, point.value);
+        self.response.push(point);
+    }
+
+    fn recommend(&self) -> usize {
+        if self.elapsed < self.baseline {
+            self.initial
+        } else {
+            self.stepped
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_first_order_plus_dead_time() {
+        let (k, tau, theta) = (50.0f32, 2.0f32, 0.5f32);
+        let mut response = StepResponse::new();
+        for tick in 0..2000 {
+            let time = tick as f32 * 0.01;
+            let since_step = time - 1.0;
+            let (output, value) = if since_step < 0.0 {
+                (10.0, 500.0)
+            } else if since_step < theta {
+                (20.0, 500.0)
+            } else {
+                (20.0, 500.0 + 10.0 * k * (1.0 - (-(since_step - theta) / tau).exp()))
+            };
+            response.push(StepPoint { time, output, value });
+        }
+
+        let model = response.fit().unwrap();
+        assert!((model.gain - k).abs() < 1.0, "{:?}", model);
+        assert!((model.time_constant.as_secs_f32() - tau).abs() < 0.1, "{:?}", model);
+        assert!((model.dead_time.as_secs_f32() - theta).abs() < 0.1, "{:?}", model);
+    }
+
+    #[test]
+    fn tuning_rules() {
+        let model = FopdtModel {
+            gain: 2.0,
+            time_constant: Duration::from_secs(4),
+            dead_time: Duration::from_secs(1),
+        };
+
+        let (p, i, d) = model.gains(StepTuningRule::ZieglerNichols);
+        assert!((p - 2.4).abs() < 1e-4);
+        assert!((i - 1.2).abs() < 1e-4);
+        assert!((d - 1.2).abs() < 1e-4);
+
+        let (p, i, d) = model.gains(StepTuningRule::Simc);
+        assert!((p - 1.0).abs() < 1e-4);
+        assert!((i - 0.25).abs() < 1e-4);
+        assert_eq!(d, 0.0);
+    }
+
+    #[test]
+    fn step_test_drives_output() {
+        let mut test = StepTest::new(1, 5)
+            .with_baseline(Duration::from_secs(1))
+            .with_duration(Duration::from_secs(1));
+        let tick = Sample { elapsed: Duration::from_millis(500), ..Sample::default() };
+
+        assert_eq!(test.recommend(), 1);
+        test.observe(tick);
+        assert_eq!(test.recommend(), 1);
+        test.observe(tick);
+        assert_eq!(test.recommend(), 5);
+        test.observe(tick);
+        test.observe(tick);
+        assert!(test.is_done());
+        assert_eq!(test.response().points().len(), 4);
+    }
+}
@@ -20,6 +20,7 @@
 //! linux. Ymmv.
 //!
 
+use crate::{StepPoint, StepResponse, Trace, TracePoint};
 use chrono;
 use fern;
 use log::LevelFilter;
@@ -81,6 +82,83 @@ pub fn filter_log(full_log: &Path, filter_string: &str, new_log_name: &str) -> R
     Ok(())
 }
 
+/// Reads back a step response logged by `StepTest` at `debug` level, so a test run can be
+/// fitted offline.
+/// ```no_run
+/// use clobber::tuning::read_step_response;
+/// use clobber::StepTuningRule;
+/// use std::path::Path;
+///
+/// let response = read_step_response(Path::new("step.log")).unwrap();
+/// let gains = response.fit().map(|model| model.gains(StepTuningRule::Simc));
+/// ```
+///
+/// Only lines mentioning `StepTest` are read, and only their last three fields matter:
+/// ```txt
+/// 11:50:19, StepTest, 12.5, 20, 1834.2
+/// ```
+pub fn read_step_response(log: &Path) -> Result<StepResponse> {
+    let log = fs::read_to_string(log)?;
+
+    let mut response = StepResponse::new();
+    for line in log.lines().filter(|s| s.contains("StepTest")) {
+        let fields = line.split(',').map(|s| s.trim()).collect::<Vec<&str>>();
+        if fields.len() < 3 {
+            continue;
+        }
+
+        // time, output, value
+        let fields = &fields[fields.len() - 3..];
+        response.push(StepPoint {
+            time: fields[0].parse()?,
+            output: fields[1].parse()?,
+            value: fields[2].parse()?,
+        });
+    }
+
+    Ok(response)
+}
+
+/// Reads a closed-loop controller trace logged by `Trace::push` back out of a log, for
+/// scoring with `Trace::analyze`.
+/// ```no_run
+/// use clobber::tuning::read_trace;
+/// use std::path::Path;
+///
+/// let trace = read_trace(Path::new("simple.log"), "Trace").unwrap();
+/// if let Some(analysis) = trace.analyze() {
+///     println!("{}", analysis);
+/// }
+/// ```
+///
+/// Only lines containing `filter_string` are read, and only their last four fields matter:
+/// ```txt
+/// 11:50:19, Trace, 12.5, 1000, 982.4, 40
+/// ```
+/// i.e. seconds since the run started, setpoint, process value and controller output.
+pub fn read_trace(log: &Path, filter_string: &str) -> Result<Trace> {
+    let log = fs::read_to_string(log)?;
+
+    let mut trace = Trace::new();
+    for line in log.lines().filter(|s| s.contains(filter_string)) {
+        let fields = line.split(',').map(|s| s.trim()).collect::<Vec<&str>>();
+        if fields.len() < 4 {
+            continue;
+        }
+
+        // time, setpoint, value, output
+        let fields = &fields[fields.len() - 4..];
+        trace.push(TracePoint {
+            time: fields[0].parse()?,
+            setpoint: fields[1].parse()?,
+            value: fields[2].parse()?,
+            output: fields[3].parse()?,
+        });
+    }
+
+    Ok(trace)
+}
+
 pub fn setup_logger(log_level: LevelFilter, path: &Path) -> Result<()> {
     let log_file = create_or_overwrite_file(path)?;
 
@@ -100,5 +178,63 @@ fn create_or_overwrite_file(path: &Path) -> Result<File> {
     // attempt to delete
     std::fs::remove_file(path).ok();
 
-    Ok(std::fs::OpenOptions::new().write(true).create(true).open(path)?)
+    Ok(std::fs::OpenOptions::new().write(true).create(true).truncate(true).open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_a_logged_step_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("step.log");
+        let mut log = create_or_overwrite_file(&path).unwrap();
+
+        // a 10 worker step into 50 rps per worker, 2s time constant and 0.5s dead time,
+        // logged the way `setup_logger` formats `StepTest` lines
+        let (k, tau, theta) = (50.0f32, 2.0f32, 0.5f32);
+        for tick in 0..1000 {
+            let time = tick as f32 * 0.02;
+            let since_step = time - 1.0;
+            let (output, value) = if since_step < 0.0 {
+                (10.0, 500.0)
+            } else if since_step < theta {
+                (20.0, 500.0)
+            } else {
+                (20.0, 500.0 + 10.0 * k * (1.0 - (-(since_step - theta) / tau).exp()))
+            };
+            writeln!(log, "11:50:19, StepTest, {}, {}, {}", time, output, value).unwrap();
+            writeln!(log, "11:50:19, PidController, {}", output).unwrap();
+        }
+
+        let response = read_step_response(&path).unwrap();
+        assert_eq!(response.points().len(), 1000);
+
+        let model = response.fit().unwrap();
+        assert!((model.gain - k).abs() < 1.0, "{:?}", model);
+        assert!((model.time_constant.as_secs_f32() - tau).abs() < 0.1, "{:?}", model);
+        assert!((model.dead_time.as_secs_f32() - theta).abs() < 0.1, "{:?}", model);
+    }
+
+    #[test]
+    fn reads_back_a_logged_trace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.log");
+        fs::write(
+            &path,
+            "11:50:19, Trace, 0, 100, 0, 10\n\
+             11:50:19, PidController, 10\n\
+             11:50:20, Trace, 1, 100, 60, 8\n\
+             11:50:21, Trace, 2, 100, 100, 5\n",
+        )
+        .unwrap();
+
+        let trace = read_trace(&path, "Trace").unwrap();
+        assert_eq!(trace.points().len(), 3);
+        assert_eq!(
+            trace.points()[1],
+            TracePoint { time: 1.0, setpoint: 100.0, value: 60.0, output: 8.0 }
+        );
+    }
 }
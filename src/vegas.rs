@@ -0,0 +1,163 @@
+use crate::controller::{ConcurrencyController, Sample};
+use log::debug;
+
+/// # VegasController
+///
+/// A limiter modelled on TCP Vegas, which watches for queueing before it turns into loss.
+///
+/// The lowest latency seen so far is taken as the no-load round trip time. Any latency
+/// above that is assumed to be time spent waiting in a queue, so the number of requests
+/// queued at the target can be estimated as `limit * (1 - min_rtt / sample_rtt)`. When the
+/// estimate is below `alpha` there is headroom and the limit grows; when it is above `beta`
+/// the target is backing up and the limit shrinks; in between it holds still.
+///
+/// Holding still is what sets it apart from `AimdController` and `GradientController`:
+/// once it finds a comfortable operating point it stops oscillating, which makes it a good
+/// comparison against `PidController` on latency-sensitive targets.
+///
+/// ```
+/// use clobber::{ConcurrencyController, Sample, VegasController};
+/// use std::time::Duration;
+///
+/// let mut vegas = VegasController::new(20);
+/// let sample = Sample { requests: 100, latency: Duration::from_millis(10), ..Sample::default() };
+///
+/// vegas.observe(sample);
+/// assert_eq!(vegas.recommend(), 21);
+///
+/// // latency doubled; half of the in-flight requests are estimated to be queued
+/// vegas.observe(Sample { latency: Duration::from_millis(20), ..sample });
+/// assert_eq!(vegas.recommend(), 20);
+/// ```
+#[derive(Debug, Clone)]
+pub struct VegasController {
+    limit: usize,
+    min_limit: usize,
+    max_limit: usize,
+    /// Lowest latency seen since the last probe, in seconds
+    min_rtt: Option<f32>,
+    /// Grow the limit while the estimated queue is below this
+    alpha: f32,
+    /// Shrink the limit while the estimated queue is above this
+    beta: f32,
+    /// Forget `min_rtt` every this many samples, 0 to never forget
+    probe_interval: usize,
+    samples: usize,
+}
+
+impl VegasController {
+    /// Creates a controller starting at `initial` workers, with an `alpha` of 3 and a
+    /// `beta` of 6 queued requests.
+    pub fn new(initial: usize) -> Self {
+        Self {
+            limit: initial,
+            min_limit: 1,
+            max_limit: usize::MAX,
+            min_rtt: None,
+            alpha: 3.0,
+            beta: 6.0,
+            probe_interval: 0,
+            samples: 0,
+        }
+    }
+
+    /// Bounds the recommendation to `[min, max]` workers.
+    pub fn with_limits(mut self, min: usize, max: usize) -> Self {
+        assert!(min <= max, "limits must satisfy min <= max");
+        self.min_limit = min;
+        self.max_limit = max;
+        self.limit = self.limit.max(min).min(max);
+        self
+    }
+
+    /// Sets the queue size thresholds. The limit grows below `alpha` and shrinks above
+    /// `beta`.
+    pub fn with_thresholds(mut self, alpha: f32, beta: f32) -> Self {
+        assert!(alpha <= beta, "thresholds must satisfy alpha <= beta");
+        self.alpha = alpha;
+        self.beta = beta;
+        self
+    }
+
+    /// Forgets the minimum RTT every `samples` samples so that a permanent change in the
+    /// target's baseline latency is eventually noticed.
+    pub fn with_probe_interval(mut self, samples: usize) -> Self {
+        self.probe_interval = samples;
+        self
+    }
+
+    /// The queue size implied by the latest sample, given the current limit.
+    fn estimate_queue(&self, rtt: f32, min_rtt: f32) -> f32 {
+        self.limit as f32 * (1.0 - min_rtt / rtt)
+    }
+}
+
+impl ConcurrencyController for VegasController {
+    fn observe(&mut self, sample: Sample) {
+        let rtt = sample.latency.as_secs_f32();
+        if sample.requests == 0 || rtt <= 0.0 {
+            return;
+        }
+
+        self.samples += 1;
+        if self.probe_interval > 0 && self.samples.is_multiple_of(self.probe_interval) {
+            self.min_rtt = None;
+        }
+
+        let min_rtt = match self.min_rtt {
+            Some(min_rtt) => min_rtt.min(rtt),
+            None => rtt,
+        };
+        self.min_rtt = Some(min_rtt);
+
+        let queue = self.estimate_queue(rtt, min_rtt);
+        if queue < self.alpha {
+            self.limit = (self.limit + 1).min(self.max_limit);
+        } else if queue > self.beta {
+            self.limit = self.limit.saturating_sub(1).max(self.min_limit);
+        }
+
+        debug!("VegasController, {}, {}", queue, self.limit);
+    }
+
+    fn recommend(&self) -> usize {
+        self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn sample(latency_ms: u64) -> Sample {
+        Sample { requests: 10, latency: Duration::from_millis(latency_ms), ..Sample::default() }
+    }
+
+    #[test]
+    fn holds_between_thresholds() {
+        let mut vegas = VegasController::new(50);
+        vegas.observe(sample(100));
+        assert_eq!(vegas.recommend(), 51);
+
+        // 51 * (1 - 100/110) is roughly 4.6 queued, between alpha and beta
+        vegas.observe(sample(110));
+        assert_eq!(vegas.recommend(), 51);
+
+        vegas.observe(sample(150));
+        assert_eq!(vegas.recommend(), 50);
+    }
+
+    #[test]
+    fn probing_rediscovers_baseline() {
+        let mut vegas = VegasController::new(50).with_probe_interval(10);
+        vegas.observe(sample(10));
+
+        // the target permanently slows down; without probing this looks like a huge queue
+        for _ in 0..30 {
+            vegas.observe(sample(100));
+        }
+
+        assert!(vegas.recommend() > 50);
+    }
+}